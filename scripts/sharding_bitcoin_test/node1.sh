@@ -1,2 +1,2 @@
 cd ../../
-RUST_BACKTRACE=1 cargo run sharding_bitcoin --p2p 127.0.0.1:6000 --api 127.0.0.1:7000 --shardId 0 --nodeId 0 --experNumber 0 --experIter 1 --shardNum 2 --shardSize 2 --blockSize 2048 --k 7 --bDiff 3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3 --oDiff  10c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c3
\ No newline at end of file
+RUST_BACKTRACE=1 cargo run sharding_bitcoin --p2p 127.0.0.1:6000 --api 127.0.0.1:7000 --shardId 0 --nodeId 0 --experNumber 0 --experIter 1 --shardNum 2 --shardSize 2 --blockSize 2048 --k 7 --bDiff 3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3cf3 --oDiff  10c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c30c3 --syntheticTxs
\ No newline at end of file
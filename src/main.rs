@@ -92,6 +92,81 @@ fn main() {
             (@arg order_diff:
                 --oDiff [STR]
                 "Sets the difficulty of mining a proposer block")
+            (@arg synthetic_txs:
+                --syntheticTxs
+                "Fills blocks with random transactions instead of the mempool")
+            (@arg retarget_window:
+                --retargetWindow [INT]
+                default_value("0")
+                "Sets the number of blocks between two difficulty retargets, 0 disables retargeting")
+            (@arg order_interval:
+                --orderInterval [INT]
+                default_value("0")
+                "Sets the expected milliseconds between two order blocks")
+            (@arg block_interval:
+                --blockInterval [INT]
+                default_value("0")
+                "Sets the expected milliseconds between two blocks of a shard")
+            (@arg max_time_drift:
+                --maxTimeDrift [INT]
+                default_value("7200000")
+                "Sets the milliseconds a block timestamp may be ahead of the local clock, 0 disables the check")
+            (@arg median_time_span:
+                --medianTimeSpan [INT]
+                default_value("11")
+                "Sets the number of ancestors whose median timestamp a block must exceed, 0 disables the check")
+            (@arg shard_assignment:
+                --shardAssignment [STR]
+                default_value("hash-mod")
+                "Sets how addresses are assigned to shards, hash-mod or prefix-range")
+            (@arg block_reward:
+                --blockReward [INT]
+                default_value("50")
+                "Sets the subsidy of a shard block, paid to its miner with the fees")
+            (@arg order_reward:
+                --orderReward [INT]
+                default_value("50")
+                "Sets the subsidy of an order block, paid to its miner")
+            (@arg coinbase_maturity:
+                --coinbaseMaturity [INT]
+                default_value("100")
+                "Sets the number of shard blocks on top of a reward before it can be spent")
+            (@arg coinbase_addr:
+                --coinbaseAddr [STR]
+                "Sets the address receiving the rewards of the mined blocks")
+            (@arg miner_threads:
+                --minerThreads [INT]
+                default_value("1")
+                "Sets the number of threads mining a block template")
+            (@arg mempool_max_txs:
+                --mempoolMaxTxs [INT]
+                default_value("100000")
+                "Sets the number of pending transactions kept by the mempool, 0 for no limit")
+            (@arg mempool_max_bytes:
+                --mempoolMaxBytes [INT]
+                default_value("100000000")
+                "Sets the memory size of the pending transactions in bytes, 0 for no limit")
+            (@arg mempool_expiry:
+                --mempoolExpiry [INT]
+                default_value("1209600")
+                "Sets the seconds a transaction may stay in the mempool, 0 for no expiry")
+            (@arg mempool_rbf:
+                --mempoolRbf
+                "Lets a transaction paying more replace the pending transactions spending the same outputs")
+            (@arg mempool_max_ancestors:
+                --mempoolMaxAncestors [INT]
+                default_value("25")
+                "Sets the number of pending ancestors a transaction of the mempool may have, 0 for no limit")
+            (@arg mempool_max_descendants:
+                --mempoolMaxDescendants [INT]
+                default_value("25")
+                "Sets the number of pending descendants a transaction of the mempool may have, 0 for no limit")
+            (@arg genesis:
+                --genesis [FILE]
+                "Builds the genesis blocks from a JSON spec, whose targets replace bDiff and oDiff")
+            (@arg data_dir:
+                --("data-dir") [DIR]
+                "Keeps the node state under the directory and reopens it on restart")
         )       
     )
     .get_matches();
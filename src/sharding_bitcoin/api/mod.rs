@@ -13,13 +13,12 @@ use crate::{
         // },
         configuration::Configuration,
         block::Info,
+        transaction::Transaction,
+    },
+    types::{
+        hash::Hashable,
+        encoding,
     },
-    // types::{
-    //     hash::{
-    //         H256,
-    //         Hashable,
-    //     }
-    // },
 };
 
 use log::{info};
@@ -99,7 +98,7 @@ impl Server {
                 let network = server.network.clone();
                 let multichain = Arc::clone(&server.multichain);
                 // let multichain = server.multichain.clone();
-                // let mempool = Arc::clone(&server.mempool);
+                let mempool = Arc::clone(&server.mempool);
                 let config = server.config.clone();
                 // let validator = Validator::new(
                 //     &multichain,
@@ -145,10 +144,60 @@ impl Server {
                             miner.exit();
                             respond_result!(req, true, "ok");
                         }
+                        "/miner/stats" => {
+                            respond_json!(req, miner.get_stats());
+                        }
                         "/network/ping" => {
                             network.broadcast(Message::Ping(String::from("Test ping")));
                             respond_result!(req, true, "ok");
                         }
+                        "/transaction/submit" => {
+                            let params = url.query_pairs();
+                            let params: HashMap<_, _> = params.into_owned().collect();
+                            let tx = match params.get("tx") {
+                                Some(v) => v,
+                                None => {
+                                    respond_result!(req, false, "missing tx");
+                                    return;
+                                }
+                            };
+                            //the transaction is in the canonical encoding, then hex
+                            let tx: Transaction = match hex::decode(tx)
+                                .map_err(|e| e.to_string())
+                                .and_then(|bytes| encoding::deserialize(&bytes)) {
+                                Ok(v) => v,
+                                Err(e) => {
+                                    respond_result!(
+                                        req,
+                                        false,
+                                        format!("error parsing tx: {}", e)
+                                    );
+                                    return;
+                                }
+                            };
+                            let tx_hash = tx.hash();
+                            let owner = config.get_shard_id_by_addr(&tx.get_sender_addr());
+                            if owner != config.shard_id {
+                                //hand it over to the shard owning the sender
+                                network.broadcast_with_shard(Message::Transactions(vec![tx]), owner);
+                                respond_result!(req, true, format!("forwarded {:?} to shard {}", tx_hash, owner));
+                                return;
+                            }
+                            let admitted = multichain
+                                .lock()
+                                .unwrap()
+                                .admit_tx(&mut mempool.lock().unwrap(), tx);
+                            if let Err(e) = admitted {
+                                respond_result!(req, false, e);
+                                return;
+                            }
+                            miner.update();
+                            network.broadcast_with_shard(
+                                Message::NewTransactionHashes(vec![tx_hash]),
+                                config.shard_id,
+                            );
+                            respond_result!(req, true, format!("{:?}", tx_hash));
+                        }
                         // "/blockchain/log" => {
                         //     let path = format!("./log/optchain/exper_{}/iter_{}/{}.txt", config.exper_number, config.exper_iter, config.shard_id*config.shard_size+config.node_id);
                         //     let mut output = File::create(path).unwrap();
@@ -212,13 +261,14 @@ impl Server {
                                         .get_order_block(&h)
                                         .unwrap();
                                     let timestamp = order_versa_block.get_timestamp();
+                                    let target = order_versa_block.get_order_target();
                                     let datetime: DateTime<Local> = timestamp.into();
                                     let formatted_datetime = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
 
                                     let str = h.to_string();
                                     let left_slice = &str[0..3];
                                     let right_slice = &str[61..64];
-                                    format!("{left_slice}..{right_slice}:{formatted_datetime}")
+                                    format!("{left_slice}..{right_slice}:{formatted_datetime}:target {target}")
                                 })
                                 .collect();
                             let prop_forking_rate = multichain
@@ -242,13 +292,14 @@ impl Server {
                                         .get_shard_block_by_shard(&h, config.shard_id)
                                         .unwrap();
                                     let timestamp = shard_versa_block.get_timestamp();
+                                    let target = shard_versa_block.get_shard_target();
                                     let datetime: DateTime<Local> = timestamp.into();
                                     let formatted_datetime = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
 
                                     let str = h.to_string();
                                     let left_slice = &str[0..3];
                                     let right_slice = &str[61..64];
-                                    format!("{left_slice}..{right_slice}:{formatted_datetime}")
+                                    format!("{left_slice}..{right_slice}:{formatted_datetime}:target {target}")
                                 })
                                 .collect();
                             let shard_forking_rate = multichain
@@ -293,17 +344,59 @@ impl Server {
                                         .get_shard_block_by_shard(&h, shard_id)
                                         .unwrap();
                                     let timestamp = avai_versa_block.get_timestamp();
+                                    let target = avai_versa_block.get_shard_target();
                                     let datetime: DateTime<Local> = timestamp.into();
                                     let formatted_datetime = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
 
                                     let str = h.to_string();
                                     let left_slice = &str[0..3];
                                     let right_slice = &str[61..64];
-                                    format!("{left_slice}..{right_slice}:{formatted_datetime}")
+                                    format!("{left_slice}..{right_slice}:{formatted_datetime}:target {target}")
                                 })
                                 .collect();
                             respond_json!(req, v_string);
                         }
+                        "/mempool/stats" => {
+                            let stats = mempool.lock().unwrap().get_stats();
+                            respond_json!(req, stats);
+                        }
+                        "/blockchain/global-ledger" => {
+                            let params = url.query_pairs();
+                            let params: HashMap<_, _> = params.into_owned().collect();
+                            let start = match params.get("start") {
+                                Some(v) => match v.parse::<usize>() {
+                                    Ok(v) => v,
+                                    Err(e) => {
+                                        respond_result!(
+                                            req,
+                                            false,
+                                            format!("error parsing start: {}", e)
+                                        );
+                                        return;
+                                    }
+                                },
+                                None => 0,
+                            };
+                            let limit = match params.get("limit") {
+                                Some(v) => match v.parse::<usize>() {
+                                    Ok(v) => v,
+                                    Err(e) => {
+                                        respond_result!(
+                                            req,
+                                            false,
+                                            format!("error parsing limit: {}", e)
+                                        );
+                                        return;
+                                    }
+                                },
+                                None => 100,
+                            };
+                            let ledger = multichain
+                                .lock()
+                                .unwrap()
+                                .get_global_ledger_page(start, limit);
+                            respond_json!(req, ledger);
+                        }
                         _ => {
                             let content_type =
                                 "Content-Type: application/json".parse::<Header>().unwrap();
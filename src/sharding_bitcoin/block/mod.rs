@@ -3,8 +3,10 @@ use serde::{Serialize, Deserialize};
 use crate::{
     types::{
         hash::{H256, Hashable}, 
+        address::Address,
         merkle::MerkleTree,
         random::Random,
+        encoding::{self, Encodable, Decodable, Reader},
     },
     sharding_bitcoin::{
         transaction::Transaction,
@@ -29,7 +31,10 @@ pub struct BlockHeader {
     order_parent: H256, 
     shard_parent: H256, 
     merkle_root: H256, 
-    timestamp: SystemTime,
+    order_target: H256, //target of the block if it becomes an order block
+    shard_target: H256, //target of the block if it becomes a shard block
+    timestamp: u64, //milliseconds since the unix epoch
+    coinbase_addr: Address, //receiver of the reward of the block
 }
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, Hash, PartialEq)]
 pub struct BlockContent {
@@ -74,7 +79,10 @@ pub trait Info {
     fn get_order_parent(&self) -> H256;
     fn get_shard_parent(&self) -> H256;
     fn get_merkle_root(&self) -> H256;
+    fn get_order_target(&self) -> H256;
+    fn get_shard_target(&self) -> H256;
     fn get_timestamp(&self) -> SystemTime;
+    fn get_coinbase_addr(&self) -> Address;
     fn get_info_hash(&self) -> Vec<H256>;
 }
 
@@ -89,6 +97,14 @@ Block Header
 ------------
 */
 
+//timestamps before the unix epoch are not representable and map to the epoch
+fn to_millis(timestamp: SystemTime) -> u64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 impl Random for BlockHeader {
     fn random() -> Self {
         let mut rng = rand::thread_rng();
@@ -101,14 +117,21 @@ impl Random for BlockHeader {
             order_parent,
             shard_parent,
             merkle_root,
-            timestamp: SystemTime::now(),
+            order_target: H256::random(),
+            shard_target: H256::random(),
+            timestamp: to_millis(SystemTime::now()),
+            coinbase_addr: Address::default(),
         }
     }
 }
 
 impl std::fmt::Debug for BlockHeader {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "BlockHeader(shard_id: {}, hash: {:?})", self.shard_id, self.hash())
+        write!(
+            f,
+            "BlockHeader(shard_id: {}, order_target: {}, shard_target: {}, hash: {:?})",
+            self.shard_id, self.order_target, self.shard_target, self.hash()
+        )
     }
 }
 
@@ -116,16 +139,35 @@ impl std::fmt::Debug for BlockHeader {
 
 impl Hashable for BlockHeader {
     fn hash(&self) -> H256 {
-        let info_vec = self.get_info_hash(); 
-        let info_hash: H256 = H256::multi_hash(&info_vec);
-        let all_hashes: Vec<H256> = vec![
-            info_hash, 
-            self.order_parent.clone(), 
-            self.shard_parent.clone(), 
-            self.merkle_root.clone(),
-            ];
-        let all_hash: H256 = H256::multi_hash(&all_hashes);
-        all_hash
+        encoding::digest(self)
+    }
+}
+
+impl Encodable for BlockHeader {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.shard_id.encode(out);
+        self.order_parent.encode(out);
+        self.shard_parent.encode(out);
+        self.merkle_root.encode(out);
+        self.order_target.encode(out);
+        self.shard_target.encode(out);
+        self.timestamp.encode(out);
+        self.coinbase_addr.encode(out);
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        Ok(BlockHeader {
+            shard_id: u32::decode(reader)?,
+            order_parent: H256::decode(reader)?,
+            shard_parent: H256::decode(reader)?,
+            merkle_root: H256::decode(reader)?,
+            order_target: H256::decode(reader)?,
+            shard_target: H256::decode(reader)?,
+            timestamp: u64::decode(reader)?,
+            coinbase_addr: Address::decode(reader)?,
+        })
     }
 }
 
@@ -140,7 +182,10 @@ impl Default for BlockHeader {
             order_parent: H256::default(),
             shard_parent: H256::default(),
             merkle_root: H256::default(),
-            timestamp: SystemTime::from(UNIX_EPOCH + Duration::new(0,0)),
+            order_target: H256::default(),
+            shard_target: H256::default(),
+            timestamp: 0,
+            coinbase_addr: Address::default(),
         }
     }
 }
@@ -152,6 +197,8 @@ impl BlockHeader {
         order_parent: H256,
         shard_parent: H256,
         merkle_root: H256,
+        order_target: H256,
+        shard_target: H256,
         // parent: H256, 
         // nonce: usize, 
         // difficulty: H256,  
@@ -167,7 +214,10 @@ impl BlockHeader {
             order_parent,
             shard_parent,
             merkle_root,
-            timestamp,
+            order_target,
+            shard_target,
+            timestamp: to_millis(timestamp),
+            coinbase_addr: Address::default(),
             // merkle_root
         }
     }
@@ -179,6 +229,9 @@ impl BlockHeader {
     pub fn set_shard_id(&mut self, shard_id: usize) {
         self.shard_id = shard_id as u32;
     }
+    pub fn set_coinbase_addr(&mut self, coinbase_addr: Address) {
+        self.coinbase_addr = coinbase_addr;
+    }
 }
 
 impl Info for BlockHeader {
@@ -195,15 +248,23 @@ impl Info for BlockHeader {
     fn get_merkle_root(&self) -> H256 {
         self.merkle_root.clone()
     }
+    fn get_order_target(&self) -> H256 {
+        self.order_target
+    }
+    fn get_shard_target(&self) -> H256 {
+        self.shard_target
+    }
     fn get_timestamp(&self) -> SystemTime {
-        self.timestamp.clone()
+        UNIX_EPOCH + Duration::from_millis(self.timestamp)
+    }
+    fn get_coinbase_addr(&self) -> Address {
+        self.coinbase_addr
     }
     
     fn get_info_hash(&self) -> Vec<H256> {
-        let time_str = format!("{:?}", self.timestamp);
         let time_hash: H256 = ring::digest::digest(
             &ring::digest::SHA256,
-            time_str.as_bytes()
+            &self.timestamp.to_be_bytes()
         ).into();
         let shard_id_hash :H256 = ring::digest::digest(
             &ring::digest::SHA256,
@@ -302,9 +363,18 @@ impl Info for ShardBlock {
     fn get_merkle_root(&self) -> H256 {
         self.header.get_merkle_root()
     }
+    fn get_order_target(&self) -> H256 {
+        self.header.get_order_target()
+    }
+    fn get_shard_target(&self) -> H256 {
+        self.header.get_shard_target()
+    }
     fn get_timestamp(&self) -> SystemTime {
         self.header.get_timestamp()
     }
+    fn get_coinbase_addr(&self) -> Address {
+        self.header.get_coinbase_addr()
+    }
     fn get_info_hash(&self) -> Vec<H256> {
         self.header.get_info_hash()
     }
@@ -331,6 +401,30 @@ impl ShardBlock {
     pub fn verify_hash(&self) -> bool {
         H256::pow_hash(&self.header.hash(), self.nonce) == self.hash
     }
+
+    /// Check that the transactions match the merkle root committed in the header
+    pub fn verify_merkle_root(&self) -> bool {
+        MerkleTree::<Transaction>::new(self.txs.data.as_slice()).root() 
+            == self.header.get_merkle_root()
+    }
+}
+
+//the hash is not encoded, it is recomputed from the header and the nonce
+impl Encodable for ShardBlock {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.header.encode(out);
+        self.txs.data.encode(out);
+        self.nonce.encode(out);
+    }
+}
+
+impl Decodable for ShardBlock {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let header = BlockHeader::decode(reader)?;
+        let txs = Vec::<Transaction>::decode(reader)?;
+        let nonce = u32::decode(reader)?;
+        Ok(ShardBlock::create(header, txs, nonce))
+    }
 }
 
 impl Content for ShardBlock {
@@ -372,9 +466,18 @@ impl Info for OrderBlock {
     fn get_merkle_root(&self) -> H256 {
         self.header.get_merkle_root()
     }
+    fn get_order_target(&self) -> H256 {
+        self.header.get_order_target()
+    }
+    fn get_shard_target(&self) -> H256 {
+        self.header.get_shard_target()
+    }
     fn get_timestamp(&self) -> SystemTime {
         self.header.get_timestamp()
     }
+    fn get_coinbase_addr(&self) -> Address {
+        self.header.get_coinbase_addr()
+    }
     fn get_info_hash(&self) -> Vec<H256> {
         self.header.get_info_hash()
     }
@@ -409,6 +512,23 @@ impl OrderBlock {
     }
 }
 
+impl Encodable for OrderBlock {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.header.encode(out);
+        self.confirmed_shard_blocks.encode(out);
+        self.nonce.encode(out);
+    }
+}
+
+impl Decodable for OrderBlock {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let header = BlockHeader::decode(reader)?;
+        let confirmed_shard_blocks = Vec::<H256>::decode(reader)?;
+        let nonce = u32::decode(reader)?;
+        Ok(OrderBlock::create(header, confirmed_shard_blocks, nonce))
+    }
+}
+
 impl Hashable for Block {
     fn hash(&self) -> H256 {
         self.hash.clone()
@@ -436,10 +556,15 @@ impl Block {
         self.content.get_txs()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn construct(
         shard_id: usize, 
         order_parent: H256, 
         shard_parent: H256,
+        order_target: H256,
+        shard_target: H256,
+        timestamp: SystemTime,
+        coinbase_addr: Address,
         txs: Vec<Transaction>,
         confirmed_shard_blocks: Vec<H256>
     ) -> Block {
@@ -453,7 +578,10 @@ impl Block {
             order_parent,
             shard_parent,
             merkle_root: merkle_tree.root(),
-            timestamp: SystemTime::now(),
+            order_target,
+            shard_target,
+            timestamp: to_millis(timestamp),
+            coinbase_addr,
         };
 
         let content: BlockContent = BlockContent {
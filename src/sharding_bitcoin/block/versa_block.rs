@@ -6,7 +6,11 @@ use crate::{
             OrderBlock,
         },
     },
-    types::hash::{H256, Hashable},
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+        encoding::{Encodable, Decodable, Reader},
+    },
 };
 use std::{
     time::SystemTime,
@@ -40,6 +44,56 @@ impl Hashable for VersaBlock {
     }
 }
 
+impl Encodable for VersaBlock {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            VersaBlock::ShardBlock(shard_block) => {
+                0u8.encode(out);
+                shard_block.encode(out);
+            }
+            VersaBlock::OrderBlock(order_block) => {
+                1u8.encode(out);
+                order_block.encode(out);
+            }
+        }
+    }
+}
+
+impl Decodable for VersaBlock {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match u8::decode(reader)? {
+            0 => Ok(VersaBlock::ShardBlock(ShardBlock::decode(reader)?)),
+            1 => Ok(VersaBlock::OrderBlock(OrderBlock::decode(reader)?)),
+            x => Err(format!("Unknown block kind {}", x)),
+        }
+    }
+}
+
+impl Encodable for VersaHash {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            VersaHash::ShardHash(hash) => {
+                0u8.encode(out);
+                hash.encode(out);
+            }
+            VersaHash::OrderHash(hash) => {
+                1u8.encode(out);
+                hash.encode(out);
+            }
+        }
+    }
+}
+
+impl Decodable for VersaHash {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match u8::decode(reader)? {
+            0 => Ok(VersaHash::ShardHash(H256::decode(reader)?)),
+            1 => Ok(VersaHash::OrderHash(H256::decode(reader)?)),
+            x => Err(format!("Unknown block kind {}", x)),
+        }
+    }
+}
+
 impl std::fmt::Debug for VersaBlock {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -66,6 +120,14 @@ impl VersaBlock {
         }
     }
 
+    /// The hash, tagged with the chain the block belongs to
+    pub fn get_versa_hash(&self) -> VersaHash {
+        match self {
+            VersaBlock::ShardBlock(_) => VersaHash::ShardHash(self.hash()),
+            VersaBlock::OrderBlock(_) => VersaHash::OrderHash(self.hash()),
+        }
+    }
+
     pub fn get_parent(&self) -> H256 {
         match self {
             VersaBlock::ShardBlock(shard_block) => shard_block.get_shard_parent(),
@@ -81,6 +143,14 @@ impl VersaBlock {
         }
     }
 
+    /// The target committed for the chain the block belongs to
+    pub fn get_target(&self) -> H256 {
+        match self {
+            VersaBlock::ShardBlock(shard_block) => shard_block.get_shard_target(),
+            VersaBlock::OrderBlock(order_block) => order_block.get_order_target(),
+        }
+    }
+
     pub fn get_timestamp(&self) -> SystemTime {
         match self {
             VersaBlock::ShardBlock(shard_block) => shard_block.get_timestamp(),
@@ -88,6 +158,13 @@ impl VersaBlock {
         }
     }
 
+    pub fn get_coinbase_addr(&self) -> Address {
+        match self {
+            VersaBlock::ShardBlock(shard_block) => shard_block.get_coinbase_addr(),
+            VersaBlock::OrderBlock(order_block) => order_block.get_coinbase_addr(),
+        }
+    }
+
     pub fn get_info_hash(&self) -> Vec<H256> {
         match self {
             VersaBlock::ShardBlock(shard_block) => shard_block.get_info_hash(),
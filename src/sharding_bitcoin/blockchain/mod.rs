@@ -23,17 +23,33 @@ pub struct Node {
     pub children: Vec<Box<Node>>,
     pub height: usize,
     pub longest_height: usize,
+    pub total_work: u128, //work of the blocks from the genesis to this one
+    pub target: H256, //the target this block is mined at
+}
+
+/// A move of the tip of a chain, a plain extension of the tip detaches nothing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reorg {
+    pub detached: Vec<H256>, //blocks leaving the longest chain, from the old tip down to the fork
+    pub attached: Vec<H256>, //blocks joining the longest chain, from the fork up to the new tip
 }
 
 pub struct Blockchain {
     hash2blk: Database<VersaBlock>, //blk_hash -> block
     //Rust does not allow two pointers to point to the same variable
     hash2node: HashMap<H256, Node>, //blk_hash -> node
+    hash2parent: HashMap<H256, H256>, //blk_hash -> parent hash, the genesis has none
+    hash2depth: HashMap<H256, usize>, //blk_hash -> blocks known on top of it, at most k
     root: Box<Node>,
+    longest_chain: Vec<H256>, //blocks of the longest chain, from the genesis to the tip
     //multiple blocks
     pub longest_chain_hash: H256,
     pub height: usize,
     pub config: Configuration,
+    //target of the genesis and expected milliseconds between two blocks of this chain
+    base_target: H256,
+    interval: u64,
+    last_reorg: Option<Reorg>, //the move of the tip caused by the last inserted block
 }
 
 //prune the branches which are not growing on the longest chain. 
@@ -51,6 +67,7 @@ impl Node {
         root: &mut Box<Node>, 
         parent: &H256, 
         hash: H256, 
+        target: H256,
         k: usize
     ) -> Option<Box<Node>>
     {
@@ -68,7 +85,9 @@ impl Node {
                 val: hash,
                 children: Vec::new(),
                 height: root.height + 1,
-                longest_height: root.height + 1
+                longest_height: root.height + 1,
+                total_work: root.total_work.saturating_add(target.to_work()),
+                target,
             });
             root.children.push(new_node.clone());
             if new_node.longest_height > root.longest_height {
@@ -78,7 +97,7 @@ impl Node {
         } else {
             let mut return_node: Option<Box<Node>> = None;
             for item in root.children.iter_mut() {
-                let sub_return_node = Self::insert(item, parent, hash, k);
+                let sub_return_node = Self::insert(item, parent, hash, target, k);
                 match sub_return_node {
                     Some(res) => {
                         //If the new node is extending the longest chain, we gonna 
@@ -256,39 +275,96 @@ impl Node {
 }
 
 impl Blockchain {
-    /// Create a new blockchain, only containing the genesis block
+    /// Create a new blockchain, only containing the genesis block.
+    /// With a data directory, the blocks stored by an earlier run are loaded as well.
     pub fn new(genesis_block: VersaBlock, config: &Configuration) -> Self {
         let genesis_hash = genesis_block.hash();
 
-        let now = SystemTime::now();
         //let mut hash2blk: HashMap<H256, VersaBlock> = HashMap::new();
-        let mut hash2blk: Database<VersaBlock> = 
-          Database::<VersaBlock>::new(format!("node(shard-{},index-{})/blockchain/hash2blk/{:?}", config.shard_id, config.node_id, now));
-        hash2blk.insert(genesis_hash.clone(), genesis_block.clone()).unwrap();
+        let mut hash2blk: Database<VersaBlock> = match &config.data_dir {
+            //a store belongs to one chain, and the order genesis may share its hash with a shard genesis
+            Some(data_dir) => {
+                let chain_name = match &genesis_block {
+                    VersaBlock::OrderBlock(_) => String::from("order"),
+                    VersaBlock::ShardBlock(shard_block) => format!("shard-{}", shard_block.get_shard_id()),
+                };
+                Database::<VersaBlock>::open(format!("{}/blockchain/{}/{}/hash2blk", data_dir, chain_name, genesis_hash))
+            }
+            None => {
+                let now = SystemTime::now();
+                Database::<VersaBlock>::new(format!("node(shard-{},index-{})/blockchain/hash2blk/{:?}", config.shard_id, config.node_id, now))
+            }
+        };
+        if !hash2blk.contains_key(&genesis_hash) {
+            hash2blk.insert(genesis_hash.clone(), genesis_block.clone()).unwrap();
+        }
 
+        let (base_target, interval) = match &genesis_block {
+            VersaBlock::OrderBlock(_) => (config.order_diff, config.order_interval),
+            VersaBlock::ShardBlock(_) => (config.block_diff, config.block_interval),
+        };
         let root = Box::new(Node {
             val: genesis_hash.clone(),
             children: Vec::new(),
             height: 0,
             longest_height: 0,
+            total_work: 0,
+            target: base_target,
         });
         let longest_chain_hash = genesis_hash.clone();
         let height = 0 as usize;
         // let verified_height = 0 as usize;
         let mut hash2node: HashMap<H256, Node> = HashMap::new();
         hash2node.insert(genesis_hash.clone(), (*root).clone());
+        let mut hash2depth: HashMap<H256, usize> = HashMap::new();
+        hash2depth.insert(genesis_hash, 0);
 
         
 
     
 
-        Blockchain {
+        let mut blockchain = Blockchain {
             hash2blk,
             hash2node,
+            hash2parent: HashMap::new(),
+            hash2depth,
             root,
+            longest_chain: vec![genesis_hash],
             longest_chain_hash,
             height,
             config: config.clone(),
+            base_target,
+            interval,
+            last_reorg: None,
+        };
+        blockchain.restore();
+        blockchain
+    }
+
+    //rebuild the tree from the stored blocks, parents before children
+    fn restore(&mut self) {
+        let genesis_hash = self.root.val;
+        let mut children: HashMap<H256, Vec<H256>> = HashMap::new();
+        for (hash, block) in self.hash2blk.iter() {
+            if hash == genesis_hash {
+                continue;
+            }
+            let parent = match block {
+                VersaBlock::OrderBlock(order_block) => order_block.get_order_parent(),
+                VersaBlock::ShardBlock(shard_block) => shard_block.get_shard_parent(),
+            };
+            children.entry(parent).or_default().push(hash);
+        }
+        let mut parents: VecDeque<H256> = VecDeque::from(vec![genesis_hash]);
+        while let Some(parent) = parents.pop_front() {
+            if let Some(mut hashes) = children.remove(&parent) {
+                hashes.sort();
+                for hash in hashes {
+                    if self.attach_block(hash, &parent).is_ok() {
+                        parents.push_back(hash);
+                    }
+                }
+            }
         }
     }
     
@@ -320,37 +396,160 @@ impl Blockchain {
             return Err(String::from("Parent doesn't exisit"));
         }
          
+        self.last_reorg = self.attach_block(blk_hash, parent)?;
+
+        //update basic information
+        self.hash2blk.insert(
+            blk_hash.clone(),
+            block.clone()
+        ).unwrap();
+            
+        Ok(true)
+    }
+
+    /// Take the move of the tip caused by the last inserted block, if any
+    pub fn take_reorg(&mut self) -> Option<Reorg> {
+        self.last_reorg.take()
+    }
+
+    /// The target of a block extending `parent`. Every `retarget_window` blocks, the target 
+    /// is scaled by the time the last window took over the expected time, by at most 4 times.
+    pub fn next_target(&self, parent: &H256) -> Option<H256> {
+        let parent_node = self.hash2node.get(parent)?;
+        let window = self.config.retarget_window;
+        if window == 0 {
+            return Some(self.base_target);
+        }
+        let height = parent_node.height + 1;
+        if height % window != 0 {
+            return Some(parent_node.target);
+        }
+        //the genesis timestamp is not a mining time
+        let first_height = cmp::max(height.saturating_sub(window), 1);
+        if first_height >= parent_node.height {
+            return Some(parent_node.target);
+        }
+        let path = self.all_blocks_end_with_block(parent)?;
+        let first_time = self.get_block(&path[first_height])?.get_timestamp();
+        let last_time = self.get_block(parent)?.get_timestamp();
+        let actual = last_time
+            .duration_since(first_time)
+            .map(|x| x.as_millis() as u64)
+            .unwrap_or(0);
+        let expected = cmp::max(self.interval * (parent_node.height - first_height) as u64, 1);
+        let actual = cmp::min(cmp::max(actual, expected / 4), expected * 4);
+        Some(parent_node.target.mul_div(cmp::max(actual, 1), expected))
+    }
+
+    /// The median timestamp of the last `median_time_span` blocks ending with `parent`,
+    /// a block extending `parent` must be strictly later
+    pub fn get_median_time_past(&self, parent: &H256) -> Option<SystemTime> {
+        let path = self.all_blocks_end_with_block(parent)?;
+        let span = cmp::max(self.config.median_time_span, 1);
+        let mut timestamps: Vec<SystemTime> = path
+            .iter()
+            .rev()
+            .take(span)
+            .map(|x| self.get_block(x).map(|blk| blk.get_timestamp()))
+            .collect::<Option<Vec<SystemTime>>>()?;
+        timestamps.sort();
+        Some(timestamps[timestamps.len() / 2])
+    }
+
+    //put a block in the tree, its content is kept in hash2blk. 
+    //returns the move of the tip when the block takes it
+    fn attach_block(&mut self, blk_hash: H256, parent: &H256) -> Result<Option<Reorg>, String> {
+        let target = match self.next_target(parent) {
+            Some(target) => target,
+            None => return Err(String::from("Parent doesn't exisit")),
+        };
         let possible_node = Node::insert(
             &mut self.root,
-            &parent,
+            parent,
             blk_hash.clone(),
+            target,
             self.config.k
         );
         if let None = possible_node {
             return Err(String::from("Insertion fail"));
         }
 
-
-
         let new_node = possible_node.unwrap();
         //update hash2node
         self.hash2node.insert(blk_hash.clone(), (*new_node).clone());
-
-        //update basic information
-        self.hash2blk.insert(
-            blk_hash.clone(),
-            block.clone()
-        ).unwrap();
-
-        //update the longest chain information
-        if new_node.height > self.height {
+        self.hash2parent.insert(blk_hash, *parent);
+        self.update_depth(&blk_hash);
+
+        //the tip is the block with the most work, the lower hash wins a tie
+        let tip_work = self.hash2node
+            .get(&self.longest_chain_hash)
+            .map(|x| x.total_work)
+            .unwrap_or(0);
+        if new_node.total_work > tip_work 
+            || (new_node.total_work == tip_work && new_node.val < self.longest_chain_hash) {
+            let reorg = self.get_reorg(&blk_hash);
+            self.longest_chain.truncate(self.longest_chain.len() - reorg.detached.len());
+            self.longest_chain.extend(reorg.attached.iter().cloned());
             self.height = new_node.height;
             self.longest_chain_hash = new_node.val.clone();
+            return Ok(Some(reorg));
         } 
+        Ok(None)
+    }
 
-        
-            
-        Ok(true)
+    //deepen the ancestors of a new block. The ancestors more than k blocks 
+    //above it were already k-deep thanks to its parent.
+    fn update_depth(&mut self, blk_hash: &H256) {
+        self.hash2depth.insert(*blk_hash, 0);
+        let mut current = *blk_hash;
+        for depth in 1..=self.config.k {
+            let parent = match self.hash2parent.get(&current) {
+                Some(parent) => *parent,
+                None => break,
+            };
+            let parent_depth = self.hash2depth.entry(parent).or_insert(0);
+            if *parent_depth >= depth {
+                break;
+            }
+            *parent_depth = depth;
+            current = parent;
+        }
+    }
+
+    //the move of the tip to a new block. A block extending the tip detaches nothing, 
+    //otherwise both chains are walked back to their fork.
+    fn get_reorg(&self, new_tip: &H256) -> Reorg {
+        let old_tip = self.longest_chain_hash;
+        if self.hash2parent.get(new_tip) == Some(&old_tip) {
+            return Reorg {
+                detached: vec![],
+                attached: vec![*new_tip],
+            };
+        }
+        let height = |hash: &H256| self.hash2node.get(hash).map(|x| x.height).unwrap_or(0);
+        let mut detached: Vec<H256> = vec![];
+        let mut attached: Vec<H256> = vec![];
+        let (mut old, mut new) = (old_tip, *new_tip);
+        while old != new {
+            //the genesis is an ancestor of both, so each walk ends there at the latest
+            if height(&old) >= height(&new) {
+                detached.push(old);
+                old = self.hash2parent[&old];
+            } else {
+                attached.push(new);
+                new = self.hash2parent[&new];
+            }
+        }
+        attached.reverse();
+        Reorg { detached, attached }
+    }
+
+    /// The work of the blocks from the genesis to the tip
+    pub fn get_total_work(&self) -> u128 {
+        self.hash2node
+            .get(&self.longest_chain_hash)
+            .map(|x| x.total_work)
+            .unwrap_or(0)
     }
 
     /// Get the last block's hash of the longest chain
@@ -360,8 +559,46 @@ impl Blockchain {
 
     /// Get all blocks' hashes of the longest chain, ordered from genesis to the tip
     pub fn all_blocks_in_longest_chain(&self) -> Vec<H256> {
-        Node::get_path(&self.root, &self.longest_chain_hash)
-                .unwrap()
+        self.longest_chain.clone()
+    }
+
+    /// Get all known blocks' hashes, parents before children
+    pub fn all_blocks(&self) -> Vec<H256> {
+        Node::pre_traverse(&self.root)
+    }
+
+    /// Whether the block is on the longest chain
+    pub fn in_longest_chain(&self, hash: &H256) -> bool {
+        match self.get_block_height(hash) {
+            Some(height) => self.longest_chain.get(height) == Some(hash),
+            None => false,
+        }
+    }
+
+    /// Whether `ancestor` is on the path from the genesis to `hash`, a block is its own ancestor
+    pub fn is_ancestor(&self, ancestor: &H256, hash: &H256) -> bool {
+        let ancestor_height = match self.get_block_height(ancestor) {
+            Some(height) => height,
+            None => return false,
+        };
+        //walk back to the longest chain, whose blocks are found by height
+        let mut current = *hash;
+        loop {
+            let height = match self.get_block_height(&current) {
+                Some(height) => height,
+                None => return false,
+            };
+            if height < ancestor_height {
+                return false;
+            }
+            if current == *ancestor {
+                return true;
+            }
+            if self.longest_chain.get(height) == Some(&current) {
+                return self.longest_chain.get(ancestor_height) == Some(ancestor);
+            }
+            current = self.hash2parent[&current];
+        }
     }
 
     
@@ -398,6 +635,14 @@ impl Blockchain {
     }
 
 
+    /// Whether k blocks are known on top of the block, on any branch
+    pub fn is_k_deep(&self, hash: &H256) -> bool {
+        match self.hash2depth.get(hash) {
+            Some(depth) => *depth >= self.config.k,
+            None => false,
+        }
+    }
+
     pub fn get_block_height(&self, block_hash: &H256) -> Option<usize> {
         match self.hash2node.get(block_hash) {
             Some(node) => {
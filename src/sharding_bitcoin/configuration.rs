@@ -1,4 +1,47 @@
-use crate::types::hash::H256;
+use crate::types::{hash::H256, address::Address};
+use std::str::FromStr;
+
+/// How addresses, and so accounts and their outputs, are assigned to shards
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShardAssignment {
+    /// The last 8 bytes of the address, modulo the number of shards
+    #[default]
+    HashMod,
+    /// The first 8 bytes of the address, the space of which is split into equal ranges
+    PrefixRange,
+}
+
+impl ShardAssignment {
+    pub fn get_shard_id(&self, addr: &[u8], shard_num: usize) -> usize {
+        if shard_num == 0 {
+            return 0;
+        }
+        let mut word = [0u8; 8];
+        let len = addr.len().min(8);
+        match self {
+            ShardAssignment::HashMod => {
+                word[8 - len..].copy_from_slice(&addr[addr.len() - len..]);
+                (u64::from_be_bytes(word) % shard_num as u64) as usize
+            }
+            ShardAssignment::PrefixRange => {
+                word[..len].copy_from_slice(&addr[..len]);
+                ((u64::from_be_bytes(word) as u128 * shard_num as u128) >> 64) as usize
+            }
+        }
+    }
+}
+
+impl FromStr for ShardAssignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash-mod" => Ok(ShardAssignment::HashMod),
+            "prefix-range" => Ok(ShardAssignment::PrefixRange),
+            _ => Err(format!("unknown shard assignment {}", s)),
+        }
+    }
+}
 
 
 #[derive(Debug, Default, Clone)]
@@ -13,6 +56,25 @@ pub struct Configuration {
     pub shard_size: usize,
     pub exper_number: usize,
     pub exper_iter: usize,
+    pub synthetic_txs: bool, //fill blocks with random transactions instead of the mempool
+    pub data_dir: Option<String>, //reopen the stores under this directory across restarts
+    pub retarget_window: usize, //number of blocks between two retargets, 0 keeps the targets fixed
+    pub order_interval: u64, //expected milliseconds between two order blocks
+    pub block_interval: u64, //expected milliseconds between two blocks of a shard
+    pub max_time_drift: u64, //milliseconds a block timestamp may be ahead of the local clock, 0 disables the check
+    pub median_time_span: usize, //number of ancestors whose median timestamp a block must exceed, 0 disables the check
+    pub shard_assignment: ShardAssignment, //which shard owns an address
+    pub block_reward: usize, //subsidy of a shard block, paid together with its fees
+    pub order_reward: usize, //subsidy of an order block
+    pub coinbase_maturity: usize, //shard blocks on top of a reward before it can be spent
+    pub coinbase_addr: Address, //receiver of the rewards of the mined blocks
+    pub miner_threads: usize, //threads sharing the nonces of a block template
+    pub mempool_max_txs: usize, //pending transactions kept by the mempool, 0 for no limit
+    pub mempool_max_bytes: usize, //memory size of the pending transactions, 0 for no limit
+    pub mempool_expiry: u64, //seconds a transaction may stay in the mempool, 0 for no expiry
+    pub mempool_rbf: bool, //let a better paying transaction replace the pending ones it conflicts with
+    pub mempool_max_ancestors: usize, //pending ancestors of a pending transaction, 0 for no limit
+    pub mempool_max_descendants: usize, //pending descendants of a pending transaction, 0 for no limit
 }
 
 impl Configuration {
@@ -28,6 +90,29 @@ impl Configuration {
             shard_size: 0,
             exper_number: 0,
             exper_iter: 0,
+            synthetic_txs: false,
+            data_dir: None,
+            retarget_window: 0,
+            order_interval: 0,
+            block_interval: 0,
+            max_time_drift: 0,
+            median_time_span: 0,
+            shard_assignment: ShardAssignment::HashMod,
+            block_reward: 0,
+            order_reward: 0,
+            coinbase_maturity: 0,
+            coinbase_addr: Address::default(),
+            miner_threads: 1,
+            mempool_max_txs: 0,
+            mempool_max_bytes: 0,
+            mempool_expiry: 0,
+            mempool_rbf: false,
+            mempool_max_ancestors: 0,
+            mempool_max_descendants: 0,
         }
     }
-}
+    /// The shard owning an address under the configured assignment
+    pub fn get_shard_id_by_addr<T: AsRef<[u8]>>(&self, addr: &T) -> usize {
+        self.shard_assignment.get_shard_id(addr.as_ref(), self.shard_num)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,156 @@
+use serde::{Serialize, Deserialize};
+use crate::{
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+        merkle::MerkleTree,
+        encoding,
+    },
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::VersaBlock,
+        },
+        configuration::Configuration,
+        transaction::{Transaction, TxFlag, UtxoInput, UtxoOutput},
+    },
+};
+use std::time::UNIX_EPOCH;
+
+/// An output minted by the genesis of its shard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub address: String, //hex of the 20 address bytes
+    pub value: u32,
+}
+
+/// The genesis of a network, loaded from a JSON file.
+/// Nodes building different genesis blocks do not peer with each other.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GenesisSpec {
+    pub chain_id: u64,
+    pub order_target: String, //hex of the initial target of the order chain
+    pub shard_target: String, //hex of the initial target of every shard chain
+    //allocations[i] is minted by the genesis of shard i
+    #[serde(default)]
+    pub allocations: Vec<Vec<Allocation>>,
+}
+
+fn parse_hex<const N: usize>(s: &str) -> Result<[u8; N], String> {
+    hex::decode(s)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| format!("{} is not {} bytes long", s, N))
+}
+
+impl GenesisSpec {
+    /// A network without allocations, using the targets of the configuration
+    pub fn new(chain_id: u64, config: &Configuration) -> Self {
+        GenesisSpec {
+            chain_id,
+            order_target: hex::encode(config.order_diff.as_ref()),
+            shard_target: hex::encode(config.block_diff.as_ref()),
+            allocations: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read the genesis spec {}: {}", path, e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Cannot parse the genesis spec {}: {}", path, e))
+    }
+
+    pub fn get_order_target(&self) -> Result<H256, String> {
+        Ok(parse_hex::<32>(&self.order_target)?.into())
+    }
+
+    pub fn get_shard_target(&self) -> Result<H256, String> {
+        Ok(parse_hex::<32>(&self.shard_target)?.into())
+    }
+
+    /// Check the spec against the configuration, every allocation must belong to its shard
+    pub fn validate(&self, config: &Configuration) -> Result<bool, String> {
+        self.get_order_target()?;
+        self.get_shard_target()?;
+        if self.allocations.len() > config.shard_num {
+            return Err(format!(
+                "Allocations for {} shards in a network of {}",
+                self.allocations.len(),
+                config.shard_num
+            ));
+        }
+        for (shard_id, allocations) in self.allocations.iter().enumerate() {
+            for allocation in allocations.iter() {
+                let addr: Address = parse_hex::<20>(&allocation.address)?.into();
+                if config.get_shard_id_by_addr(&addr) != shard_id {
+                    return Err(format!(
+                        "Allocation to {} does not belong to shard {}",
+                        allocation.address,
+                        shard_id
+                    ));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    //the parents of the genesis blocks commit to the chain id
+    fn get_chain_hash(&self) -> H256 {
+        encoding::digest(&self.chain_id)
+    }
+
+    fn create_header(&self, shard_id: usize, merkle_root: H256) -> BlockHeader {
+        BlockHeader::create(
+            shard_id,
+            self.get_chain_hash(),
+            self.get_chain_hash(),
+            merkle_root,
+            self.get_order_target().unwrap_or_default(),
+            self.get_shard_target().unwrap_or_default(),
+            UNIX_EPOCH,
+        )
+    }
+
+    /// The transaction minting the allocations of a shard, none without allocations
+    pub fn get_allocation_tx(&self, shard_id: usize) -> Option<Transaction> {
+        let outputs: Vec<UtxoOutput> = self.allocations
+            .get(shard_id)?
+            .iter()
+            .filter_map(|x| Some(UtxoOutput {
+                receiver_addr: parse_hex::<20>(&x.address).ok()?.into(),
+                value: x.value,
+            }))
+            .collect();
+        if outputs.is_empty() {
+            return None;
+        }
+        Some(Transaction {
+            inputs: vec![UtxoInput::default()],
+            outputs,
+            flag: TxFlag::Initial,
+        })
+    }
+
+    pub fn get_order_genesis(&self) -> VersaBlock {
+        let header = self.create_header(0, MerkleTree::<Transaction>::new(&[]).root());
+        VersaBlock::OrderBlock(OrderBlock::create(header, vec![], 0))
+    }
+
+    pub fn get_shard_genesis(&self, shard_id: usize) -> VersaBlock {
+        let txs: Vec<Transaction> = self.get_allocation_tx(shard_id)
+            .into_iter()
+            .collect();
+        let header = self.create_header(shard_id, MerkleTree::<Transaction>::new(&txs).root());
+        VersaBlock::ShardBlock(ShardBlock::create(header, txs, 0))
+    }
+
+    /// Digest of the hashes of all the genesis blocks, compared by peers when they connect
+    pub fn get_genesis_hash(&self, shard_num: usize) -> H256 {
+        let mut hashes: Vec<H256> = vec![self.get_order_genesis().hash()];
+        hashes.extend((0..shard_num).map(|i| self.get_shard_genesis(i).hash()));
+        encoding::digest(&hashes)
+    }
+}
@@ -7,29 +7,141 @@ use crate::{
     },
     sharding_bitcoin::{
         configuration::Configuration,
-        transaction::Transaction,
+        transaction::{Transaction, TxFlag},
+        state::{Outpoint, UtxoState},
     },
 };
-use std::collections::{VecDeque};
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 // use log::{info, debug};
-use std::time::{SystemTime};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+//seconds since the unix epoch
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+//the outputs spent by a transaction, minted ones spend nothing
+fn get_outpoints(tx: &Transaction) -> Vec<Outpoint> {
+    match tx.flag {
+        TxFlag::Domestic | TxFlag::Input => tx.inputs
+            .iter()
+            .map(|x| (x.tx_hash, x.index))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+//fee paid per 1000 bytes
+fn get_rate(fee: u64, size: usize) -> u64 {
+    (fee as u128 * 1000 / size.max(1) as u128) as u64
+}
+
+//why a transaction is turned away by the mempool
+enum Rejection {
+    Duplicate,
+    TooLarge,
+    ChainLimit,
+    Conflict,
+    LowFee,
+}
+
+/// A pending transaction with the fee it pays, computed against the UTXO state
+/// when it entered the mempool
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub fee: u64,
+    pub time: u64, //seconds since the unix epoch when the transaction entered the mempool
+}
+
+impl Hashable for MempoolEntry {
+    fn hash(&self) -> H256 {
+        self.tx.hash()
+    }
+}
+
+impl MempoolEntry {
+    /// Fee paid per 1000 bytes of the transaction
+    pub fn get_fee_rate(&self) -> u64 {
+        get_rate(self.fee, self.tx.get_mem_size())
+    }
+}
+
+/// Transactions turned away or dropped by the mempool, by reason
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MempoolStats {
+    pub size: usize,
+    pub bytes: usize,
+    pub invalid: usize, //failed the validation against the state
+    pub duplicate: usize,
+    pub too_large: usize, //larger than the whole mempool
+    pub low_fee: usize, //the mempool is full of better paying transactions
+    pub conflict: usize, //spends an output spent by a pending transaction it cannot replace
+    pub chain_limit: usize, //too many pending ancestors, or descendants for one of them
+    pub replaced: usize, //dropped, with its descendants, for a better paying conflict
+    pub evicted: usize, //dropped to make room for a better paying transaction
+    pub expired: usize, //pending for longer than the expiry
+    pub confirmed: usize, //packed in a block joining the longest chain
+    pub double_spent: usize, //dropped, with its descendants, spending an output spent by a confirmed transaction
+    pub restored: usize, //back from a block leaving the longest chain
+}
 
 pub struct Mempool {
-    tx_map: Database<Transaction>, //the key is the hash of the tx block, while value is the
-    //full block
-    tx_queue: VecDeque<H256>,
+    tx_map: Database<MempoolEntry>, //the key is the hash of the tx, while value is the
+    //tx with its fee
+    //(fee rate, tx hash), the best paying transaction is the last one
+    priority: BTreeSet<(u64, H256)>,
+    //(entry time, tx hash), the oldest transaction is the first one
+    arrivals: BTreeSet<(u64, H256)>,
+    spends: HashMap<Outpoint, H256>, //the pending transaction spending each output
+    bytes: usize, //memory size of all the pending transactions
+    max_txs: usize, //0 for no limit
+    max_bytes: usize, //0 for no limit
+    expiry: u64, //seconds a transaction may stay, 0 to keep it until it is mined
+    rbf: bool, //whether a conflicting transaction may replace a lower paying one
+    max_ancestors: usize, //pending ancestors of a transaction, 0 for no limit
+    max_descendants: usize, //pending descendants of a transaction, 0 for no limit
+    stats: MempoolStats,
 }
 
 
 impl Mempool {
     pub fn new(config: &Configuration) -> Self {
-        let now = SystemTime::now();
-        let tx_map: Database<Transaction> = 
-            Database::<Transaction>::new(format!("node(shard-{},index-{})/mempool/tx_map/{:?}", config.shard_id, config.node_id, now));
+        let tx_map: Database<MempoolEntry> = match &config.data_dir {
+            Some(data_dir) => Database::<MempoolEntry>::open(format!("{}/mempool/entries", data_dir)),
+            None => {
+                let now = SystemTime::now();
+                Database::<MempoolEntry>::new(format!("node(shard-{},index-{})/mempool/tx_map/{:?}", config.shard_id, config.node_id, now))
+            }
+        };
+        //transactions left by an earlier run are queued again
+        let mut priority: BTreeSet<(u64, H256)> = BTreeSet::new();
+        let mut arrivals: BTreeSet<(u64, H256)> = BTreeSet::new();
+        let mut spends: HashMap<Outpoint, H256> = HashMap::new();
+        let mut bytes = 0;
+        for (hash, entry) in tx_map.iter() {
+            priority.insert((entry.get_fee_rate(), hash));
+            arrivals.insert((entry.time, hash));
+            spends.extend(get_outpoints(&entry.tx).into_iter().map(|x| (x, hash)));
+            bytes += entry.tx.get_mem_size();
+        }
         Mempool {
             tx_map,
-            tx_queue: VecDeque::new(),
+            priority,
+            arrivals,
+            spends,
+            bytes,
+            max_txs: config.mempool_max_txs,
+            max_bytes: config.mempool_max_bytes,
+            expiry: config.mempool_expiry,
+            rbf: config.mempool_rbf,
+            max_ancestors: config.mempool_max_ancestors,
+            max_descendants: config.mempool_max_descendants,
+            stats: MempoolStats::default(),
         }
     }
 
@@ -38,83 +150,461 @@ impl Mempool {
     }
 
     pub fn get_queue_size(&self) -> usize {
-        self.tx_queue.len()
+        self.priority.len()
+    }
+
+    /// Memory size of all the pending transactions
+    pub fn get_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn get_stats(&self) -> MempoolStats {
+        MempoolStats {
+            size: self.get_size(),
+            bytes: self.bytes,
+            ..self.stats.clone()
+        }
+    }
+
+    /// Count a transaction turned away by the validation before reaching the mempool
+    pub fn record_invalid(&mut self) {
+        self.stats.invalid += 1;
+    }
+
+    /// Drop the transactions which entered the mempool before `now` minus the expiry
+    pub fn expire(&mut self, now: u64) -> usize {
+        if self.expiry == 0 {
+            return 0;
+        }
+        let expired: Vec<H256> = self.arrivals
+            .iter()
+            .take_while(|(time, _)| time.saturating_add(self.expiry) < now)
+            .map(|(_, hash)| *hash)
+            .collect();
+        let removed: usize = expired
+            .iter()
+            .map(|x| self.remove_with_descendants(x))
+            .sum();
+        self.stats.expired += removed;
+        removed
+    }
+
+    //whether the mempool exceeds its limits with `txs` more transactions of `bytes` more bytes
+    fn is_over(&self, txs: usize, bytes: usize) -> bool {
+        (self.max_txs > 0 && txs > self.max_txs)
+            || (self.max_bytes > 0 && bytes > self.max_bytes)
+    }
+
+    //remove a transaction from the store and the index
+    fn take(&mut self, hash: &H256) -> Option<MempoolEntry> {
+        let entry = self.tx_map.get(hash)?;
+        self.tx_map.remove(hash);
+        self.priority.remove(&(entry.get_fee_rate(), *hash));
+        self.arrivals.remove(&(entry.time, *hash));
+        for outpoint in get_outpoints(&entry.tx) {
+            self.spends.remove(&outpoint);
+        }
+        self.bytes -= entry.tx.get_mem_size();
+        Some(entry)
+    }
+
+    /// The pending transaction spending an output
+    pub fn get_spender(&self, outpoint: &Outpoint) -> Option<H256> {
+        self.spends.get(outpoint).cloned()
+    }
+
+    //the pending transactions spending the outputs of the transaction, 
+    //their descendants, and so on
+    fn get_descendants(&self, hash: &H256) -> Vec<H256> {
+        let mut descendants: Vec<H256> = vec![];
+        let mut visited: HashSet<H256> = HashSet::new();
+        let mut stack: Vec<H256> = vec![*hash];
+        while let Some(parent) = stack.pop() {
+            let outputs = match self.tx_map.get(&parent) {
+                Some(entry) => entry.tx.outputs.len(),
+                None => continue,
+            };
+            for index in 0..outputs as u32 {
+                if let Some(child) = self.spends.get(&(parent, index)) {
+                    if visited.insert(*child) {
+                        descendants.push(*child);
+                        stack.push(*child);
+                    }
+                }
+            }
+        }
+        descendants
+    }
+
+    //remove a transaction with its descendants, which cannot be mined without it
+    fn remove_with_descendants(&mut self, hash: &H256) -> usize {
+        let mut removed = self.get_descendants(hash);
+        removed.push(*hash);
+        removed
+            .iter()
+            .filter(|x| self.take(x).is_some())
+            .count()
+    }
+
+    //the pending transactions whose outputs the transaction spends, their ancestors, 
+    //and so on, parents before children
+    fn get_ancestors(&self, tx: &Transaction) -> Vec<H256> {
+        let mut ancestors: Vec<H256> = vec![];
+        let mut visited: HashSet<H256> = HashSet::new();
+        self.visit_ancestors(tx, &mut visited, &mut ancestors);
+        ancestors
+    }
+
+    fn visit_ancestors(&self, tx: &Transaction, visited: &mut HashSet<H256>, ancestors: &mut Vec<H256>) {
+        for (parent, _) in get_outpoints(tx) {
+            if !visited.insert(parent) {
+                continue;
+            }
+            if let Some(entry) = self.tx_map.get(&parent) {
+                self.visit_ancestors(&entry.tx, visited, ancestors);
+                ancestors.push(parent);
+            }
+        }
+    }
+
+    /// The pending transactions a transaction depends on, parents before children
+    pub fn get_ancestor_txs(&self, tx: &Transaction) -> Vec<Transaction> {
+        self.get_ancestors(tx)
+            .iter()
+            .filter_map(|x| self.get_tx(x))
+            .collect()
+    }
+
+    //a transaction joins a chain of pending transactions within the ancestor and descendant limits
+    fn check_chain_limits(&self, hash: &H256, ancestors: &[H256]) -> Result<bool, String> {
+        if self.max_ancestors > 0 && ancestors.len() > self.max_ancestors {
+            return Err(format!(
+                "{:?} has {} pending ancestors, more than {}",
+                hash,
+                ancestors.len(),
+                self.max_ancestors
+            ));
+        }
+        if self.max_descendants > 0 {
+            for ancestor in ancestors.iter() {
+                if self.get_descendants(ancestor).len() >= self.max_descendants {
+                    return Err(format!(
+                        "{:?} would give {:?} more than {} pending descendants",
+                        hash,
+                        ancestor,
+                        self.max_descendants
+                    ));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    //a transaction with its pending ancestors which are not `selected` yet, parents first
+    fn get_package(&self, hash: &H256, selected: &HashSet<H256>) -> Vec<H256> {
+        let mut package: Vec<H256> = match self.tx_map.get(hash) {
+            Some(entry) => self.get_ancestors(&entry.tx)
+                .into_iter()
+                .filter(|x| !selected.contains(x))
+                .collect(),
+            None => vec![],
+        };
+        package.push(*hash);
+        package
+    }
+
+    //fee paid per 1000 bytes by the transactions of a package together
+    fn get_package_rate(&self, package: &[H256]) -> u64 {
+        let (fee, size) = package
+            .iter()
+            .filter_map(|x| self.tx_map.get(x))
+            .fold((0, 0), |(fee, size), x| (fee + x.fee, size + x.tx.get_mem_size()));
+        get_rate(fee, size)
     }
-    
+
+    //the pending transactions a new transaction would replace, its direct conflicts 
+    //first, then their descendants
+    fn get_replaced(&self, tx: &Transaction) -> Vec<H256> {
+        let mut replaced: Vec<H256> = vec![];
+        for outpoint in get_outpoints(tx) {
+            if let Some(conflict) = self.spends.get(&outpoint) {
+                if !replaced.contains(conflict) {
+                    replaced.push(*conflict);
+                }
+            }
+        }
+        let conflicts = replaced.len();
+        for i in 0..conflicts {
+            for descendant in self.get_descendants(&replaced[i]) {
+                if !replaced.contains(&descendant) {
+                    replaced.push(descendant);
+                }
+            }
+        }
+        replaced
+    }
+
+    //a replacement must pay a higher fee rate than each transaction it conflicts with, 
+    //and more fees than all the transactions it evicts together
+    fn check_replacement(&self, entry: &MempoolEntry, replaced: &[H256]) -> Result<bool, String> {
+        let hash = entry.tx.hash();
+        if !self.rbf {
+            return Err(format!("{:?} spends an output spent by {:?}", hash, replaced[0]));
+        }
+        let replaced: Vec<MempoolEntry> = replaced
+            .iter()
+            .filter_map(|x| self.tx_map.get(x))
+            .collect();
+        if entry.tx.inputs.iter().any(|x| replaced.iter().any(|y| y.tx.hash() == x.tx_hash)) {
+            return Err(format!("{:?} spends an output of a transaction it replaces", hash));
+        }
+        let conflicts: Vec<&MempoolEntry> = replaced
+            .iter()
+            .filter(|x| get_outpoints(&x.tx).iter().any(|y| get_outpoints(&entry.tx).contains(y)))
+            .collect();
+        if conflicts.iter().any(|x| x.get_fee_rate() >= entry.get_fee_rate()) {
+            return Err(format!("{:?} pays too low a fee rate to replace its conflicts", hash));
+        }
+        let fees: u64 = replaced.iter().map(|x| x.fee).sum();
+        if entry.fee <= fees {
+            return Err(format!(
+                "{:?} pays a fee of {}, not more than the {} of the transactions it replaces",
+                hash,
+                entry.fee,
+                fees
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Take up to `num` transactions, see `get_entries`
     pub fn get_txs(&mut self, num: usize) -> Result<Vec<Transaction>, Vec<Transaction>> {
-        let mut txs: Vec<Transaction> = vec![];
-        if num >= self.tx_queue.len() {
-            while !self.tx_queue.is_empty() {
-                let hash = self.tx_queue.pop_front().unwrap();
-                let tx = self.tx_map.get(&hash).unwrap().clone();
-                self.tx_map.remove(&hash);
-                txs.push(tx);
-            }
-            Ok(txs)
-        } else {
-            for _i in 0..num {
-                let hash = self.tx_queue.pop_front().unwrap();
-                let tx = self.tx_map.get(&hash).unwrap().clone();
-                self.tx_map.remove(&hash);
-                txs.push(tx);
+        let into_txs = |entries: Vec<MempoolEntry>| entries
+            .into_iter()
+            .map(|x| x.tx)
+            .collect();
+        self.get_entries(num)
+            .map(into_txs)
+            .map_err(into_txs)
+    }
+
+    /// Take up to `num` transactions with their entries. A transaction comes with its 
+    /// pending ancestors, placed before it, and the packages paying the best fee rate 
+    /// together come first, so a child paying a high fee pulls its parents in. 
+    /// Ok when nothing is left behind.
+    pub fn get_entries(&mut self, num: usize) -> Result<Vec<MempoolEntry>, Vec<MempoolEntry>> {
+        self.expire(now_secs());
+        let mut selected: Vec<H256> = vec![];
+        let mut chosen: HashSet<H256> = HashSet::new();
+        let mut left = false;
+        //(package fee rate, tx hash), computed lazily as ancestors get selected
+        let mut scores: BTreeSet<(u64, H256)> = self.priority
+            .iter()
+            .map(|(_, hash)| (self.get_package_rate(&self.get_package(hash, &chosen)), *hash))
+            .collect();
+        while let Some((score, hash)) = scores.pop_last() {
+            if chosen.contains(&hash) {
+                continue;
             }
-            Err(txs)
+            let package = self.get_package(&hash, &chosen);
+            let rate = self.get_package_rate(&package);
+            if rate != score {
+                scores.insert((rate, hash));
+                continue;
+            }
+            if selected.len() + package.len() > num {
+                left = true;
+                if selected.len() == num {
+                    break;
+                }
+                continue;
+            }
+            chosen.extend(package.iter().cloned());
+            selected.extend(package);
+        }
+        let entries: Vec<MempoolEntry> = selected
+            .iter()
+            .filter_map(|x| self.take(x))
+            .collect();
+        if left {
+            Err(entries)
+        } else {
+            Ok(entries)
         }
-        
     }
 
-    pub fn insert_tx(&mut self, tx: Transaction) -> bool {
-        let hash = tx.hash();
+    /// Add a transaction paying `fee`, which may spend the outputs of pending transactions. 
+    /// A transaction spending an output already spent by a pending one is rejected, 
+    /// unless replace-by-fee is enabled and it pays more. When the mempool is full, 
+    /// the lowest paying transactions are evicted for it with their descendants, 
+    /// if they all pay a lower fee rate.
+    pub fn insert_tx(&mut self, tx: Transaction, fee: u64) -> Result<bool, String> {
+        self.expire(now_secs());
+        let entry = MempoolEntry { tx, fee, time: now_secs() };
+        self.admit(entry).map_err(|(rejection, e)| {
+            match rejection {
+                Rejection::Duplicate => self.stats.duplicate += 1,
+                Rejection::TooLarge => self.stats.too_large += 1,
+                Rejection::ChainLimit => self.stats.chain_limit += 1,
+                Rejection::Conflict => self.stats.conflict += 1,
+                Rejection::LowFee => self.stats.low_fee += 1,
+            }
+            e
+        })
+    }
+
+    /// Put back an entry taken from the mempool, keeping the time it entered and its fee. 
+    /// It is admitted like a new transaction, but it is not one, so a rejection is not counted.
+    pub fn restore(&mut self, entry: MempoolEntry) -> Result<bool, String> {
+        self.expire(now_secs());
+        self.admit(entry).map_err(|(_, e)| e)
+    }
+
+    fn admit(&mut self, entry: MempoolEntry) -> Result<bool, (Rejection, String)> {
+        let hash = entry.tx.hash();
         if self.tx_map.contains_key(&hash) {
-            //block already exists.
-            false
-        } else {
-            self.tx_map.insert(hash.clone(), tx.clone()).unwrap();
-            self.tx_queue.push_back(hash);
-            true
+            return Err((Rejection::Duplicate, format!("{:?} already in the mempool", hash)));
+        }
+        let size = entry.tx.get_mem_size();
+        if self.is_over(1, size) {
+            return Err((Rejection::TooLarge, format!("{:?} is larger than the mempool", hash)));
         }
+        let ancestors = self.get_ancestors(&entry.tx);
+        if let Err(e) = self.check_chain_limits(&hash, &ancestors) {
+            return Err((Rejection::ChainLimit, e));
+        }
+        let replaced = self.get_replaced(&entry.tx);
+        if !replaced.is_empty() {
+            if let Err(e) = self.check_replacement(&entry, &replaced) {
+                return Err((Rejection::Conflict, e));
+            }
+        }
+        let fee_rate = entry.get_fee_rate();
+        let mut evicted: Vec<H256> = vec![];
+        let mut txs = self.get_size() + 1 - replaced.len();
+        let mut bytes = self.bytes + size - replaced
+            .iter()
+            .map(|x| self.tx_map.get(x).unwrap().tx.get_mem_size())
+            .sum::<usize>();
+        for (rate, victim) in self.priority.iter() {
+            if !self.is_over(txs, bytes) {
+                break;
+            }
+            //the new transaction cannot be mined without its ancestors
+            if replaced.contains(victim) || evicted.contains(victim) || ancestors.contains(victim) {
+                continue;
+            }
+            if *rate >= fee_rate {
+                return Err((Rejection::LowFee, format!("{:?} pays too low a fee rate for a full mempool", hash)));
+            }
+            let mut package = vec![*victim];
+            package.extend(self.get_descendants(victim));
+            package.retain(|x| !replaced.contains(x) && !evicted.contains(x));
+            let package: Vec<MempoolEntry> = package
+                .iter()
+                .filter_map(|x| self.tx_map.get(x))
+                .collect();
+            //a descendant paying more keeps its ancestors
+            if package.iter().any(|x| x.get_fee_rate() >= fee_rate) {
+                continue;
+            }
+            for x in package.iter() {
+                txs -= 1;
+                bytes -= x.tx.get_mem_size();
+                evicted.push(x.tx.hash());
+            }
+        }
+        if self.is_over(txs, bytes) {
+            return Err((Rejection::LowFee, format!("{:?} pays too low a fee rate for a full mempool", hash)));
+        }
+        for victim in replaced.iter() {
+            self.take(victim);
+        }
+        self.stats.replaced += replaced.len();
+        for victim in evicted.iter() {
+            self.take(victim);
+        }
+        self.stats.evicted += evicted.len();
+        self.priority.insert((fee_rate, hash));
+        self.arrivals.insert((entry.time, hash));
+        self.spends.extend(get_outpoints(&entry.tx).into_iter().map(|x| (x, hash)));
+        self.bytes += size;
+        self.tx_map.insert(hash, entry).unwrap();
+        Ok(true)
     }
 
     pub fn check(&self, hash: &H256) -> bool {
         self.tx_map.contains_key(hash)
     }
-        
+
     pub fn get_tx(&self, hash: &H256) -> Option<Transaction> {
-        if self.check(hash) {
-            Some(self.tx_map.get(hash).unwrap().clone())
-        } else {
-            None
-        }
+        self.tx_map.get(hash).map(|x| x.tx)
+    }
+
+    pub fn get_entry(&self, hash: &H256) -> Option<MempoolEntry> {
+        self.tx_map.get(hash)
     }
 
     pub fn get_all_txs(&self) -> Vec<Transaction> {
 
         self.tx_map
             .iter()
-            .map(|(_, val)| val.clone())
+            .map(|(_, val)| val.tx)
             .collect()
 
     }
 
+    /// Follow a move of the shard tip. The transactions of the attached blocks are confirmed, 
+    /// so they leave with the pending ones spending the same outputs and their descendants. 
+    /// The transactions of the detached blocks come back if they are valid on the new tip, 
+    /// on top of the pending ones they depend on.
+    pub fn reconcile(&mut self, detached: &[Transaction], attached: &[Transaction], state: &UtxoState) {
+        for tx in attached.iter() {
+            if self.take(&tx.hash()).is_some() {
+                self.stats.confirmed += 1;
+            }
+            for outpoint in get_outpoints(tx) {
+                if let Some(conflict) = self.get_spender(&outpoint) {
+                    self.stats.double_spent += self.remove_with_descendants(&conflict);
+                }
+            }
+        }
+        self.restore_txs(detached, state);
+    }
+
+    /// Bring back the transactions of blocks which left the shard chain, those valid on the 
+    /// state on top of the pending ones they depend on. They are not new submissions either, 
+    /// so a rejection is not counted, only the ones restored are.
+    pub fn restore_txs(&mut self, txs: &[Transaction], state: &UtxoState) {
+        //settlement steps and rewards are rebuilt by the miners
+        for tx in txs.iter().filter(|x| !x.flag.is_generated()) {
+            let ancestors = self.get_ancestor_txs(tx);
+            if self.check(&tx.hash()) || state.validate_tx_with_ancestors(tx, &ancestors).is_err() {
+                continue;
+            }
+            let fee = state.get_fee_with_ancestors(tx, &ancestors).unwrap_or(0);
+            let entry = MempoolEntry { tx: tx.clone(), fee, time: now_secs() };
+            if self.restore(entry).is_ok() {
+                self.stats.restored += 1;
+            }
+        }
+    }
+
     pub fn delete_txs(&mut self, tx_hashs: Vec<H256>) -> bool {
         for hash in tx_hashs.iter() {
-            self.tx_map.remove(&hash);
-            self.tx_queue.retain(|x| x != hash);
+            self.take(hash);
         }
         true
     }
 
-    
 
+
+    /// Take the best paying transaction without pending ancestors
     pub fn pop_one_tx(&mut self) -> Option<Transaction> {
-        if self.tx_queue.is_empty() {
-            None
-        } else {
-            let hash = self.tx_queue.pop_front().unwrap();
-            let tx_blk = self.tx_map.get(&hash).unwrap().clone();
-            self.tx_map.remove(&hash);
-            Some(tx_blk)
-        }
+        match self.get_txs(1) {
+            Ok(txs) => txs,
+            Err(txs) => txs,
+        }.pop()
     }
 
     pub fn get_all_tx_hash(&self) -> Vec<H256> {
@@ -123,6 +613,5 @@ impl Mempool {
             .map(|(key, _)| key.clone())
             .collect()
     }
-    
-}
 
+}
@@ -1,28 +1,27 @@
 pub mod worker;
+pub mod solver;
 
 use log::{info};
 use crossbeam::channel::{
     unbounded, 
     Receiver, 
     Sender, 
-    TryRecvError
+    RecvTimeoutError
 };
 use std::{
     time::{self}, 
     thread, 
     sync::{Arc, Mutex},
+    collections::HashSet,
 };
 use crate::{        
     types::{
+        address::Address,
         hash::{H256, Hashable},
         random::Random,
     }, 
     sharding_bitcoin::{
         block::{
-            Content,
-            BlockContent,
-            ShardBlock,
-            OrderBlock,
             Block,
             versa_block::VersaBlock,
         },
@@ -32,20 +31,23 @@ use crate::{
         //     Validator,
         // },
         configuration::Configuration,
-        mempool::Mempool,
+        mempool::{Mempool, MempoolEntry},
     },
 };
-use rand::Rng;
+use solver::{MiningState, MinerStats};
+
+//how often the hash rate is measured while mining
+const STATS_INTERVAL: time::Duration = time::Duration::from_secs(1);
 
 enum ControlSignal {
-    Start(u64), // the number controls the lambda of interval between block generation
+    Start(u64), // the number of microseconds each mining thread sleeps between two hashes
     Update, // update the block in mining, it may due to new blockchain tip or new transaction
     Exit,
 }
 
 enum OperatingState {
     Paused,
-    Run(u64),
+    Run,
     ShutDown,
 }
 
@@ -58,12 +60,15 @@ pub struct Context {
     mempool: Arc<Mutex<Mempool>>,
     // validator: Validator,
     config: Configuration,
+    state: Arc<MiningState>, //the template shared with the mining threads
+    outdated: bool, //a new tip or new transactions arrived since the template was built
 }
 
 #[derive(Clone)]
 pub struct Handle {
     /// Channel for sending signal to the miner thread
     control_chan: Sender<ControlSignal>,
+    state: Arc<MiningState>,
 }
 
 pub fn new(multichain: &Arc<Mutex<Multichain>>, 
@@ -74,6 +79,7 @@ pub fn new(multichain: &Arc<Mutex<Multichain>>,
     let (finished_block_sender, finished_block_receiver) = unbounded();
 
     // let validator = Validator::new(multichain, mempool, config);
+    let state = Arc::new(MiningState::new(config.miner_threads.max(1)));
 
     let ctx = Context {
         control_chan: signal_chan_receiver,
@@ -82,11 +88,14 @@ pub fn new(multichain: &Arc<Mutex<Multichain>>,
         multichain: Arc::clone(multichain),
         mempool: Arc::clone(mempool),
         // validator,
-        config: config.clone()
+        config: config.clone(),
+        state: Arc::clone(&state),
+        outdated: false,
     };
 
     let handle = Handle {
         control_chan: signal_chan_sender,
+        state,
     };
 
     (ctx, handle, finished_block_receiver)
@@ -107,8 +116,14 @@ impl Handle {
             .unwrap();
     }
 
+    /// Tell the miner about a new tip or new transactions, so it rebuilds its template
     pub fn update(&self) {
-        self.control_chan.send(ControlSignal::Update).unwrap();
+        //the miner may have exited already
+        let _ = self.control_chan.send(ControlSignal::Update);
+    }
+
+    pub fn get_stats(&self) -> MinerStats {
+        self.state.get_stats()
     }
 }
 
@@ -120,6 +135,7 @@ pub enum DoubleSpentType {
 
 impl Context {
     pub fn start(mut self) {
+        solver::spawn(self.config.miner_threads.max(1), &self.state, &self.finished_block_chan);
         thread::Builder::new()
             .name("miner".to_string())
             .spawn(move || {
@@ -129,167 +145,177 @@ impl Context {
         info!("Miner initialized into paused mode");
     }
 
-    fn po_w(&self, block_hash: H256, nonce: u32) -> H256 {
-        H256::pow_hash(&block_hash, nonce)
+
+    /// Take the pending steps of cross-shard transfers and order block rewards, then 
+    /// transactions from the mempool, which are valid on top of the shard tip. 
+    /// The reward of the block itself comes first. The mempool entries of the 
+    /// transactions kept come along, to be put back if the block is not mined.
+    fn assemble_txs(&self, multichain: &Multichain) -> (Vec<Transaction>, Vec<MempoolEntry>) {
+        let mut candidates = multichain.get_settlement_txs(self.config.shard_id);
+        candidates.truncate(self.config.block_size);
+        let mut entries = match self.mempool
+            .lock()
+            .unwrap()
+            .get_entries(self.config.block_size - candidates.len()) {
+            Ok(entries) => entries,
+            Err(entries) => entries,
+        };
+        candidates.extend(entries.iter().map(|x| x.tx.clone()));
+        let state = multichain.get_shard_state(self.config.shard_id);
+        // transactions already in the shard chain or invalid are dropped
+        let mut txs = if candidates.is_empty() {
+            candidates
+        } else {
+            state.select_txs(candidates)
+        };
+        // the reward can only be paid to an address of this shard
+        let coinbase_addr = self.config.coinbase_addr;
+        if coinbase_addr != Address::default()
+            && self.config.get_shard_id_by_addr(&coinbase_addr) == self.config.shard_id {
+            if let Some(reward) = state.create_block_reward(&txs, &coinbase_addr) {
+                txs.insert(0, reward);
+            }
+        }
+        //the invalid ones are dropped for good
+        let kept: HashSet<H256> = txs.iter().map(|x| x.hash()).collect();
+        entries.retain(|x| kept.contains(&x.tx.hash()));
+        (txs, entries)
+    }
+
+    //put the mempool entries of a template back as they were taken, parents before children. 
+    //settlement steps and rewards are rebuilt with the next template
+    fn return_entries(&self, entries: Vec<MempoolEntry>) {
+        let mut mempool = self.mempool.lock().unwrap();
+        for entry in entries {
+            //the mempool may have filled up with better paying transactions meanwhile
+            let _ = mempool.restore(entry);
+        }
+    }
+
+    /// The next template, built from a single snapshot of the chains: the tips it 
+    /// extends, the shard blocks it confirms, its targets and its transactions, 
+    /// with the mempool entries of these transactions
+    fn build_template(&self) -> (Block, Vec<MempoolEntry>) {
+        let multichain = self.multichain.lock().unwrap();
+        let order_parent = multichain.get_highest_order_block();
+        let shard_parent = multichain.get_highest_shard_block(self.config.shard_id);
+        let confirmed_shard_blocks = multichain.get_new_confirmed_shard_blocks();
+        let (txs, entries) = if self.config.synthetic_txs {
+            // randomly generate a constant number of transactions
+            let txs: Vec<Transaction> = (0..self.config.block_size)
+                .map(|_| {
+                    Transaction::random()
+                }).collect();
+            (txs, vec![])
+        } else {
+            self.assemble_txs(&multichain)
+        };
+        
+        //targets of the chains the template extends
+        let order_diff = multichain
+            .get_next_order_target(&order_parent)
+            .unwrap_or(self.config.order_diff);
+        let block_diff = multichain
+            .get_next_shard_target(self.config.shard_id, &shard_parent)
+            .unwrap_or(self.config.block_diff);
+        //the timestamp must exceed the median time past of both chains
+        let timestamp = [
+            multichain.get_order_median_time_past(&order_parent),
+            multichain.get_shard_median_time_past(
+                self.config.shard_id,
+                &shard_parent,
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|x| x + time::Duration::from_millis(1))
+        .fold(time::SystemTime::now(), std::cmp::max);
+        let template = Block::construct(
+            self.config.shard_id,
+            order_parent,
+            shard_parent,
+            order_diff,
+            block_diff,
+            timestamp,
+            self.config.coinbase_addr,
+            txs,
+            confirmed_shard_blocks,
+        );
+        (template, entries)
+    }
+
+    //stop mining the current template, its transactions go back unless they were mined
+    fn retire_job(&self, job_id: u64, entries: &mut Vec<MempoolEntry>) {
+        let entries = std::mem::take(entries);
+        if self.state.retire(job_id) {
+            self.return_entries(entries);
+        }
     }
 
+    fn handle_signal(&mut self, signal: ControlSignal) {
+        match signal {
+            ControlSignal::Exit => {
+                info!("Miner shutting down");
+                self.operating_state = OperatingState::ShutDown;
+            }
+            ControlSignal::Start(i) => {
+                info!("Miner starting in continuous mode with lambda {}", i);
+                self.state.set_lambda(i);
+                self.operating_state = OperatingState::Run;
+                self.outdated = true;
+            }
+            ControlSignal::Update => {
+                // in paused state, the template is built once the miner starts
+                self.outdated = true;
+            }
+        }
+    }
 
+    /// The threads mine the published template, while this loop follows the control 
+    /// signals and publishes a new template when told about a new tip or new transactions,
+    /// or when the threads run out of nonces
     fn miner_loop(&mut self) {
         // main mining loop
-        let mut pre_order_parent = H256::default();
-        let mut pre_shard_parent = H256::default();
-        let mut pre_global_parents = H256::default();
-        let mut pre_hybrid_block = Block::default();
+        let mut entries: Vec<MempoolEntry> = vec![];
+        let mut job_id: u64 = 0;
         loop {
             // check and react to control signals
-            // store the hash of parents in the previous round, 
-            // if the parents does not change, just need to change the nonce
-            // if the parents change, repackage the txs and generate the new consensus block
             match self.operating_state {
                 OperatingState::Paused => {
                     let signal = self.control_chan.recv().unwrap();
-                    match signal {
-                        ControlSignal::Exit => {
-                            info!("Miner shutting down");
-                            self.operating_state = OperatingState::ShutDown;
-                        }
-                        ControlSignal::Start(i) => {
-                            info!("Miner starting in continuous mode with lambda {}", i);
-                            self.operating_state = OperatingState::Run(i);
-                        }
-                        ControlSignal::Update => {
-                            // in paused state, don't need to update
-                        }
-                    };
+                    self.handle_signal(signal);
                     continue;
                 }
                 OperatingState::ShutDown => {
+                    self.retire_job(job_id, &mut entries);
+                    self.state.shutdown();
                     return;
                 }
-                _ => match self.control_chan.try_recv() {
-                    Ok(signal) => {
-                        match signal {
-                            ControlSignal::Exit => {
-                                info!("Miner shutting down");
-                                self.operating_state = OperatingState::ShutDown;
-                            }
-                            ControlSignal::Start(i) => {
-                                info!("Miner starting in continuous mode with lambda {}", i);
-                                self.operating_state = OperatingState::Run(i);
-                            }
-                            ControlSignal::Update => {
-                                unimplemented!()
-                            }
-                        };
-                    }
-                    Err(TryRecvError::Empty) => {}
-                    Err(TryRecvError::Disconnected) => panic!("Miner control channel detached"),
-                },
+                _ => {}
             }
-            if let OperatingState::ShutDown = self.operating_state {
-                return;
+            if self.outdated {
+                self.outdated = false;
+                // the previous template is abandoned, unless a thread found a block for it
+                self.retire_job(job_id, &mut entries);
+                let (template, template_entries) = self.build_template();
+                entries = template_entries;
+                job_id = self.state.publish(template);
             }
 
-
-            if let OperatingState::Run(i) = self.operating_state {
-                if i != 0 {
-                    let interval = time::Duration::from_micros(i as u64);
-                    thread::sleep(interval);
-                }
-                let shard_parent = self.multichain
-                    .lock()
-                    .unwrap()
-                    .get_highest_shard_block(self.config.shard_id);
-                let order_parent = self.multichain
-                    .lock()
-                    .unwrap()
-                    .get_highest_order_block();
-                let global_parents = self.multichain
-                    .lock()
-                    .unwrap()
-                    .get_all_highest_shard_blocks();
-                let global_parents_hash = H256::multi_hash(&(
-                    global_parents.iter()
-                                  .map(|(hash, _)| hash.clone())
-                                  .collect()
-                    ));
-                // let mut txs: Vec<Transaction> = vec![];
-                
-                //check if parents have been change
-                if shard_parent != pre_shard_parent ||
-                    order_parent != pre_order_parent ||
-                    global_parents_hash != pre_global_parents {
-                    
-                    // randomly generate a constant number of transactions
-                    let txs: Vec<Transaction> = (0..self.config.block_size)
-                                .into_iter()
-                                .map(|_| {
-                                    Transaction::random()
-                                }).collect();
-
-
-                    let confirmed_shard_blocks = self.multichain
-                        .lock()
-                        .unwrap()
-                        .get_new_confirmed_shard_blocks();
-                    
-                    // let mut supposed_global_parents = global_parents.clone();
-                    // supposed_global_parents.retain(|x| x.1 != self.config.shard_id );
-                    // supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id));
-                    let hybrid_block = Block::construct(
-                        self.config.shard_id,
-                        order_parent.clone(),
-                        shard_parent.clone(),
-                        txs,
-                        confirmed_shard_blocks,
-                    );
-                    //info!("mines a block with parent {:?} of state size: {}", last_blk_hash, last_state.len());
-                    //update related information
-                    pre_order_parent = order_parent;
-                    pre_shard_parent = shard_parent;
-                    pre_global_parents = global_parents_hash;
-                    pre_hybrid_block = hybrid_block;
-                }
-                
-                let nonce: u32 = rand::thread_rng().gen();
-                let hash_val = self.po_w(pre_hybrid_block.hash(), nonce);
-                //info!("block hash: {:?}", hash_val);
-                let block_diff = self.config.block_diff;
-                // let mut supposed_global_parents = global_parents.clone();
-                // supposed_global_parents.retain(|x| x.1 != self.config.shard_id );
-                // supposed_global_parents.push((vec![last_blk_hash.clone()], self.config.shard_id));
-                if hash_val <= block_diff {
-                    if hash_val <= self.config.order_diff {
-                        info!("mine an order block {:?} in shard {}", hash_val, self.config.shard_id);
-                        let in_block = VersaBlock::OrderBlock(OrderBlock::create(    
-                            pre_hybrid_block.get_header(),
-                            pre_hybrid_block.get_confirmed_shard_blocks(),
-                            nonce,
-                        ));
-                        self.finished_block_chan
-                            .send(MinerMessage::VersaBlk(in_block))
-                            .unwrap();
-                    } else {
-                        info!("mine a shard block {:?} in shard {}", hash_val, self.config.shard_id);
-                        let ex_block = VersaBlock::ShardBlock(ShardBlock::create(    
-                            pre_hybrid_block.get_header(),
-                            pre_hybrid_block.get_txs(),
-                            nonce,
-                        ));
-                        self.finished_block_chan
-                            .send(MinerMessage::VersaBlk(ex_block))
-                            .unwrap();
-                    }
-                    pre_order_parent = H256::default();
-                    pre_shard_parent = H256::default();
-                    pre_global_parents = H256::default();
-                    pre_hybrid_block = Block::default();
-                } else {
-                    //no block is mined
-                }
-
-                
+            match self.control_chan.recv_timeout(STATS_INTERVAL) {
+                Ok(signal) => self.handle_signal(signal),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => panic!("Miner control channel detached"),
+            }
+            // the notifications queued meanwhile are served by a single template
+            while let Ok(signal) = self.control_chan.try_recv() {
+                self.handle_signal(signal);
+            }
+            //the threads ran out of nonces, a new template has a new timestamp
+            if self.state.is_exhausted(job_id) {
+                self.outdated = true;
             }
+            self.state.update_stats();
         }
     }
 }
@@ -0,0 +1,231 @@
+use log::{info};
+use crossbeam::channel::Sender;
+use serde::Serialize;
+use std::{
+    time::{self, Instant},
+    thread,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+use crate::{
+    types::hash::{H256, Hashable},
+    sharding_bitcoin::{
+        block::{
+            Info,
+            ShardBlock,
+            OrderBlock,
+            Block,
+            versa_block::VersaBlock,
+        },
+        miner::MinerMessage,
+    },
+};
+
+//hashes a thread tries before reporting them to the statistics
+const HASH_BATCH: u64 = 1024;
+//how long an idle thread waits before looking for a new job
+const IDLE_WAIT: time::Duration = time::Duration::from_millis(1);
+
+/// A block template mined by all the threads at once
+pub struct Job {
+    pub id: u64,
+    pub block: Block,
+    pub hash: H256,
+}
+
+/// Hash rate of the mining threads
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MinerStats {
+    pub threads: usize,
+    pub hashes: u64, //hashes tried since the node started
+    pub hash_rate: f64, //hashes per second, measured over the last second or so
+    pub blocks: u64, //blocks found
+}
+
+/// The job shared by the miner with its threads. The generation is the id of the job
+/// being mined, moving it on interrupts the threads, and only the one moving it from
+/// the id of a job may hand in a block for that job.
+pub struct MiningState {
+    job: RwLock<Option<Arc<Job>>>,
+    generation: AtomicU64,
+    exhausted: AtomicU64, //the last job a thread tried all its nonces for
+    lambda: AtomicU64, //microseconds a thread sleeps between two hashes, 0 for none
+    hashes: AtomicU64,
+    blocks: AtomicU64,
+    shutdown: AtomicBool,
+    stats: Mutex<(MinerStats, Instant)>, //with the time the hash rate was last measured
+}
+
+impl MiningState {
+    pub fn new(threads: usize) -> Self {
+        MiningState {
+            job: RwLock::new(None),
+            generation: AtomicU64::new(0),
+            exhausted: AtomicU64::new(0),
+            lambda: AtomicU64::new(0),
+            hashes: AtomicU64::new(0),
+            blocks: AtomicU64::new(0),
+            shutdown: AtomicBool::new(false),
+            stats: Mutex::new((
+                MinerStats {
+                    threads,
+                    ..MinerStats::default()
+                },
+                Instant::now(),
+            )),
+        }
+    }
+
+    pub fn set_lambda(&self, lambda: u64) {
+        self.lambda.store(lambda, Ordering::Relaxed);
+    }
+
+    /// Hand a new template to the threads, returning the id of its job
+    pub fn publish(&self, block: Block) -> u64 {
+        let mut job = self.job.write().unwrap();
+        let id = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let hash = block.hash();
+        *job = Some(Arc::new(Job { id, block, hash }));
+        id
+    }
+
+    /// Stop mining the job. False when a thread found a block for it first.
+    pub fn retire(&self, id: u64) -> bool {
+        self.generation
+            .compare_exchange(id, id + 1, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Whether the job is still being mined
+    pub fn is_current(&self, id: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == id
+    }
+
+    /// Whether a thread tried all its nonces for the job, which needs a new template then
+    pub fn is_exhausted(&self, id: u64) -> bool {
+        self.exhausted.load(Ordering::SeqCst) == id
+    }
+
+    /// Stop the threads for good
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Measure the hash rate again, at most once a second
+    pub fn update_stats(&self) {
+        let mut stats = self.stats.lock().unwrap();
+        let elapsed = stats.1.elapsed().as_secs_f64();
+        if elapsed < 1.0 {
+            return;
+        }
+        let hashes = self.hashes.load(Ordering::Relaxed);
+        stats.0.hash_rate = (hashes - stats.0.hashes) as f64 / elapsed;
+        stats.0.hashes = hashes;
+        stats.1 = Instant::now();
+    }
+
+    pub fn get_stats(&self) -> MinerStats {
+        let stats = self.stats.lock().unwrap();
+        MinerStats {
+            hashes: self.hashes.load(Ordering::Relaxed),
+            blocks: self.blocks.load(Ordering::Relaxed),
+            ..stats.0.clone()
+        }
+    }
+
+    //the job to mine, none while paused or after a block was found
+    fn get_job(&self) -> Option<Arc<Job>> {
+        self.job
+            .read()
+            .unwrap()
+            .clone()
+            .filter(|x| self.is_current(x.id))
+    }
+}
+
+//the order block or the shard block a hash below one of the targets of the template seals
+fn seal(block: &Block, hash_val: H256, nonce: u32) -> Option<VersaBlock> {
+    let header = block.get_header();
+    let shard_id = header.get_shard_id();
+    if hash_val <= header.get_order_target() {
+        info!("mine an order block {:?} in shard {}", hash_val, shard_id);
+        Some(VersaBlock::OrderBlock(OrderBlock::create(
+            header,
+            block.get_confirmed_shard_blocks(),
+            nonce,
+        )))
+    } else if hash_val <= header.get_shard_target() {
+        info!("mine a shard block {:?} in shard {}", hash_val, shard_id);
+        Some(VersaBlock::ShardBlock(ShardBlock::create(
+            header,
+            block.get_txs(),
+            nonce,
+        )))
+    } else {
+        None
+    }
+}
+
+/// Start `threads` mining threads, thread `i` tries the nonces `i`, `i + threads`,
+/// `i + 2 * threads`... of every job until the job changes
+pub fn spawn(threads: usize, state: &Arc<MiningState>, finished_block_chan: &Sender<MinerMessage>) {
+    for index in 0..threads {
+        let state = Arc::clone(state);
+        let finished_block_chan = finished_block_chan.clone();
+        thread::Builder::new()
+            .name(format!("miner-{}", index))
+            .spawn(move || {
+                mining_loop(index as u64, threads as u64, &state, &finished_block_chan);
+            })
+            .unwrap();
+    }
+}
+
+fn mining_loop(index: u64, threads: u64, state: &MiningState, finished_block_chan: &Sender<MinerMessage>) {
+    while !state.shutdown.load(Ordering::SeqCst) {
+        let job = match state.get_job() {
+            Some(job) => job,
+            None => {
+                thread::sleep(IDLE_WAIT);
+                continue;
+            }
+        };
+        let mut hashes: u64 = 0;
+        let mut nonce = index;
+        while nonce <= u32::MAX as u64 && state.is_current(job.id) {
+            let lambda = state.lambda.load(Ordering::Relaxed);
+            if lambda != 0 {
+                thread::sleep(time::Duration::from_micros(lambda));
+            }
+            let hash_val = H256::pow_hash(&job.hash, nonce as u32);
+            hashes += 1;
+            if hashes == HASH_BATCH {
+                state.hashes.fetch_add(hashes, Ordering::Relaxed);
+                hashes = 0;
+            }
+            if let Some(block) = seal(&job.block, hash_val, nonce as u32) {
+                //another thread may have found a block for the same job
+                if state.retire(job.id) {
+                    state.blocks.fetch_add(1, Ordering::Relaxed);
+                    finished_block_chan
+                        .send(MinerMessage::VersaBlk(block))
+                        .unwrap();
+                }
+                break;
+            }
+            nonce += threads;
+        }
+        state.hashes.fetch_add(hashes, Ordering::Relaxed);
+        //the nonces of this thread are exhausted, the miner publishes a new template
+        //with a later timestamp once it notices
+        if nonce > u32::MAX as u64 {
+            state.exhausted.store(job.id, Ordering::SeqCst);
+        }
+        while state.is_current(job.id) && !state.shutdown.load(Ordering::SeqCst) {
+            thread::sleep(IDLE_WAIT);
+        }
+    }
+}
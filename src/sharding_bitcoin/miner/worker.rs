@@ -15,7 +15,10 @@ use crate::{
             message::Message,
         },
         multichain::Multichain,
-        miner::MinerMessage,
+        miner::{
+            MinerMessage,
+            Handle as MinerHandle,
+        },
         configuration::Configuration,
         mempool::Mempool,
     }
@@ -29,6 +32,7 @@ use std::{
 pub struct Worker {
     server: ServerHandle,
     finished_block_chan: Receiver<MinerMessage>,
+    miner: MinerHandle,
     multichain: Arc<Mutex<Multichain>>,
     mempool: Arc<Mutex<Mempool>>,
     config: Configuration,
@@ -38,6 +42,7 @@ impl Worker {
     pub fn new(
         server: &ServerHandle,
         finished_block_chan: Receiver<MinerMessage>,
+        miner: &MinerHandle,
         multichain: &Arc<Mutex<Multichain>>,
         mempool: &Arc<Mutex<Mempool>>,
         config: &Configuration,
@@ -45,6 +50,7 @@ impl Worker {
         Self {
             server: server.clone(),
             finished_block_chan,
+            miner: miner.clone(),
             multichain: Arc::clone(multichain),
             mempool: Arc::clone(mempool),
             config: config.clone(),
@@ -72,19 +78,19 @@ impl Worker {
                     match versa_block.clone() {
                         VersaBlock::ShardBlock(shard_block) => {
                             //exclusive avaialbility block
-                            let shard_parent = shard_block.get_shard_parent();
-                            match self.multichain
-                                .lock()
-                                .unwrap()
-                                .insert_block_with_parent(
-                                versa_block.clone(),
-                                &VersaHash::ShardHash(shard_parent)
+                            //the mempool follows the new tip, or keeps the transactions 
+                            //if a peer block took the tip first
+                            let mut multichain = self.multichain.lock().unwrap();
+                            match multichain.insert_mined_shard_block(
+                                shard_block,
+                                &mut self.mempool.lock().unwrap()
                             ) {
                                 Ok(_) => {}
                                 Err(e) => {
                                     info!("inserting myself fail: {}", e);
                                 }
                             }
+                            drop(multichain);
                             self.server.broadcast(
                                 Message::Blocks(vec![versa_block])
                             );
@@ -108,6 +114,8 @@ impl Worker {
                             );
                         }
                     }
+                    //the next template extends the block just mined
+                    self.miner.update();
                 }    
             }
 
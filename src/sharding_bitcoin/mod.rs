@@ -5,28 +5,23 @@ pub mod miner;
 pub mod network;
 pub mod transaction;
 pub mod configuration;
-// pub mod validator;
+pub mod validator;
 pub mod mempool;
 pub mod multichain;
+pub mod state;
+pub mod genesis;
 
 use crate::{
     types::{
-        hash::{
-            H256,
-        },
+        address::Address,
     },
     sharding_bitcoin::{
-        configuration::Configuration,
+        configuration::{Configuration, ShardAssignment},
         mempool::Mempool,
-        block::{
-            BlockHeader,
-            ShardBlock, 
-            OrderBlock,
-            versa_block::VersaBlock,
-        },
         network::{
             server as NetworkServer,
             worker::Worker as NetworkWorker,
+            message::Message,
         },
         api::Server as ApiServer,
         miner::{
@@ -35,6 +30,7 @@ use crate::{
         },
         blockchain::Blockchain as Blockchain,
         multichain::Multichain,
+        genesis::GenesisSpec,
     },
 };
 
@@ -155,22 +151,146 @@ pub fn start(sub_com: &clap::ArgMatches) {
             error!("Error parsing the confirmation depth: {}", e);
             process::exit(1);
         });
-    let block_diff = sub_com
-        .value_of("block_diff")
+    //the targets are only needed without a genesis spec
+    let block_diff = sub_com.value_of("block_diff");
+    let order_diff = sub_com.value_of("order_diff");
+    let retarget_window = sub_com
+        .value_of("retarget_window")
         .unwrap()
-        .parse::<String>()
+        .parse::<usize>()
         .unwrap_or_else(|e| {
-            error!("Error parsing the shard size: {}", e);
+            error!("Error parsing the retarget window: {}", e);
             process::exit(1);
         });
-    let order_diff = sub_com
-        .value_of("order_diff")
+    let order_interval = sub_com
+        .value_of("order_interval")
         .unwrap()
-        .parse::<String>()
+        .parse::<u64>()
         .unwrap_or_else(|e| {
-            error!("Error parsing the shard size: {}", e);
+            error!("Error parsing the order block interval: {}", e);
+            process::exit(1);
+        });
+    let block_interval = sub_com
+        .value_of("block_interval")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the shard block interval: {}", e);
+            process::exit(1);
+        });
+    let max_time_drift = sub_com
+        .value_of("max_time_drift")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the max time drift: {}", e);
+            process::exit(1);
+        });
+    let median_time_span = sub_com
+        .value_of("median_time_span")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the median time span: {}", e);
             process::exit(1);
         });
+    let shard_assignment = sub_com
+        .value_of("shard_assignment")
+        .unwrap()
+        .parse::<ShardAssignment>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the shard assignment: {}", e);
+            process::exit(1);
+        });
+    let block_reward = sub_com
+        .value_of("block_reward")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the block reward: {}", e);
+            process::exit(1);
+        });
+    let order_reward = sub_com
+        .value_of("order_reward")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the order reward: {}", e);
+            process::exit(1);
+        });
+    let coinbase_maturity = sub_com
+        .value_of("coinbase_maturity")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the coinbase maturity: {}", e);
+            process::exit(1);
+        });
+    let miner_threads = sub_com
+        .value_of("miner_threads")
+        .unwrap()
+        .parse::<usize>()
+        .ok()
+        .filter(|x| *x > 0)
+        .unwrap_or_else(|| {
+            error!("Error parsing the miner threads: a positive number is needed");
+            process::exit(1);
+        });
+    let mempool_max_txs = sub_com
+        .value_of("mempool_max_txs")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the mempool transaction limit: {}", e);
+            process::exit(1);
+        });
+    let mempool_max_bytes = sub_com
+        .value_of("mempool_max_bytes")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the mempool size limit: {}", e);
+            process::exit(1);
+        });
+    let mempool_expiry = sub_com
+        .value_of("mempool_expiry")
+        .unwrap()
+        .parse::<u64>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the mempool expiry: {}", e);
+            process::exit(1);
+        });
+    let mempool_max_ancestors = sub_com
+        .value_of("mempool_max_ancestors")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the mempool ancestor limit: {}", e);
+            process::exit(1);
+        });
+    let mempool_max_descendants = sub_com
+        .value_of("mempool_max_descendants")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap_or_else(|e| {
+            error!("Error parsing the mempool descendant limit: {}", e);
+            process::exit(1);
+        });
+    //without an address the rewards of the mined blocks are not claimed
+    let coinbase_addr: Address = match sub_com.value_of("coinbase_addr") {
+        Some(addr) => {
+            let bytes: [u8; 20] = Some(addr)
+                .filter(|x| x.len() == 40)
+                .and_then(|x| decode_hex(x).ok())
+                .and_then(|x| x.try_into().ok())
+                .unwrap_or_else(|| {
+                    error!("Error parsing the coinbase address: {}", addr);
+                    process::exit(1);
+                });
+            bytes.into()
+        }
+        None => Address::default(),
+    };
     let p2p_workers = sub_com
         .value_of("p2p_workers")
         .unwrap()
@@ -182,17 +302,6 @@ pub fn start(sub_com: &clap::ArgMatches) {
     
     
     let mut config = Configuration::new();
-    let block_diff_bytes: [u8; 32] = decode_hex(block_diff.as_str())
-        .unwrap()
-        .try_into().unwrap();
-    let order_diff_bytes: [u8; 32] = decode_hex(order_diff.as_str())
-        .unwrap()
-        .try_into().unwrap();
-    let block_diff_hash: H256 = block_diff_bytes.into();
-    let order_diff_hash: H256 = order_diff_bytes.into();
-
-    config.block_diff = block_diff_hash;
-    config.order_diff = order_diff_hash;
     config.block_size = block_size as usize;
     config.k = confirmation_depth as usize;
     config.shard_id = shard_id as usize;
@@ -201,27 +310,70 @@ pub fn start(sub_com: &clap::ArgMatches) {
     config.exper_iter = exper_iter as usize;
     config.shard_num = shard_num as usize;
     config.shard_size = shard_size as usize;
+    config.synthetic_txs = sub_com.is_present("synthetic_txs");
+    config.data_dir = sub_com.value_of("data_dir").map(|x| x.to_owned());
+    config.retarget_window = retarget_window;
+    config.order_interval = order_interval;
+    config.block_interval = block_interval;
+    config.max_time_drift = max_time_drift;
+    config.median_time_span = median_time_span;
+    config.shard_assignment = shard_assignment;
+    config.block_reward = block_reward;
+    config.order_reward = order_reward;
+    config.coinbase_maturity = coinbase_maturity;
+    config.coinbase_addr = coinbase_addr;
+    config.miner_threads = miner_threads;
+    config.mempool_max_txs = mempool_max_txs;
+    config.mempool_max_bytes = mempool_max_bytes;
+    config.mempool_expiry = mempool_expiry;
+    config.mempool_rbf = sub_com.is_present("mempool_rbf");
+    config.mempool_max_ancestors = mempool_max_ancestors;
+    config.mempool_max_descendants = mempool_max_descendants;
+    //the genesis spec fixes the initial targets, without it they come from the flags
+    let genesis_spec = match sub_com.value_of("genesis") {
+        Some(path) => GenesisSpec::load(path).unwrap_or_else(|e| {
+            error!("{}", e);
+            process::exit(1);
+        }),
+        None => {
+            let (block_diff, order_diff) = match (block_diff, order_diff) {
+                (Some(block_diff), Some(order_diff)) => (block_diff, order_diff),
+                _ => {
+                    error!("The targets are needed without a genesis spec");
+                    process::exit(1);
+                }
+            };
+            let block_diff_bytes: [u8; 32] = decode_hex(block_diff)
+                .unwrap()
+                .try_into().unwrap();
+            let order_diff_bytes: [u8; 32] = decode_hex(order_diff)
+                .unwrap()
+                .try_into().unwrap();
+            config.block_diff = block_diff_bytes.into();
+            config.order_diff = order_diff_bytes.into();
+            GenesisSpec::new(0, &config)
+        }
+    };
+    if let Err(e) = genesis_spec.validate(&config) {
+        error!("Invalid genesis spec: {}", e);
+        process::exit(1);
+    }
+    config.order_diff = genesis_spec.get_order_target().unwrap();
+    config.block_diff = genesis_spec.get_shard_target().unwrap();
+    if config.retarget_window > 0 && (config.order_interval == 0 || config.block_interval == 0) {
+        error!("Retargeting needs both the order and the shard block intervals");
+        process::exit(1);
+    }
     // let shard_id = format!("{:x}", shard_id);
     info!("configuration: {:?}", config);
 
     // let api_port: u16 = api_addr.port();
-    let order_genesis_block = OrderBlock::default();
-    let order_chain = Blockchain::new(VersaBlock::OrderBlock(order_genesis_block), &config);
+    let order_chain = Blockchain::new(genesis_spec.get_order_genesis(), &config);
 
 
     let shard_chains: Vec<Blockchain> = (0..config.shard_num)
         .into_iter()
-        .map(|i| {
-            let mut header = BlockHeader::default();
-            header.set_shard_id(i);
-            let shard_block = ShardBlock::create(
-                header,
-                vec![],
-                0,
-            );
-            let shard_genesis_block = VersaBlock::ShardBlock(shard_block);
-            Blockchain::new(shard_genesis_block, &config)
-        })
+        .map(|i| Blockchain::new(genesis_spec.get_shard_genesis(i), &config))
         .collect();
     // let chains_ref: Vec<&Arc<Mutex<Blockchain>>> = avai_chains
     //     .iter()
@@ -242,14 +394,25 @@ pub fn start(sub_com: &clap::ArgMatches) {
     let (msg_tx, msg_rx) = channel::bounded(10000);
 
     // start the p2p server
-    let (server_ctx, server) = NetworkServer::new(p2p_addr, msg_tx, config.shard_id).unwrap();
+    let genesis_hash = genesis_spec.get_genesis_hash(config.shard_num);
+    info!("genesis hash: {}", genesis_hash);
+    let (server_ctx, server) = NetworkServer::new(
+        p2p_addr,
+        msg_tx,
+        config.shard_id,
+        genesis_hash,
+    ).unwrap();
     server_ctx.start().unwrap();
     
+    // the network worker tells the miner about new tips and transactions
+    let (miner_ctx, miner, finished_block_chan) = Miner::new(&multichain, &mempool, &config);
+
     // start the worker
     let worker_ctx = NetworkWorker::new(
         p2p_workers,
         msg_rx,
         &server,
+        &miner,
         &multichain,
         &mempool,
         &config,
@@ -257,10 +420,10 @@ pub fn start(sub_com: &clap::ArgMatches) {
     worker_ctx.start();
 
     // start the miner
-    let (miner_ctx, miner, finished_block_chan) = Miner::new(&multichain, &mempool, &config);
     let miner_worker_ctx = MinerWorker::new(
         &server, 
         finished_block_chan, 
+        &miner,
         &multichain,
         &mempool,
         &config,
@@ -294,8 +457,10 @@ pub fn start(sub_com: &clap::ArgMatches) {
                         }
                     };
                     match server.connect(addr) {
-                        Ok(_) => {
+                        Ok(mut peer) => {
                             info!("Connected to outgoing peer {}", &addr);
+                            //catch up with the blocks mined while we were away
+                            peer.write(Message::GetTips);
                             break;
                         }
                         Err(e) => {
@@ -1,8 +1,12 @@
 use crate::{
     sharding_bitcoin::{
-        blockchain::Blockchain,
+        blockchain::{Blockchain, Reorg},
         configuration::Configuration,
+        mempool::Mempool,
+        state::UtxoState,
+        transaction::{Transaction, TxFlag},
         block::{
+            Content,
             Info,
             versa_block::{VersaHash, VersaBlock},
             OrderBlock,
@@ -11,6 +15,7 @@ use crate::{
     },
     types::{
         hash::{H256, Hashable},
+        address::Address,
         database::Database,
     }
 };
@@ -18,15 +23,60 @@ use crate::{
 //     // sync::{Arc, Mutex},
 //     collections::BTreeSet,
 // };
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
 use std::time::{SystemTime};
+use log::error;
 
 
+/// Position of a transaction in the global ledger, ordered field by field
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LedgerPosition {
+    pub order_height: usize,
+    pub shard_id: usize,
+    pub shard_height: usize,
+    pub tx_index: usize,
+}
+
+/// A transaction of the global ledger, with the shard block containing it
+#[derive(Serialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub position: LedgerPosition,
+    pub shard_block: H256,
+    pub tx_hash: H256,
+    pub tx: Transaction,
+}
+
+//a shard block placed in the global ledger, ordered by shard id then height
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct LedgerBlock {
+    shard_id: usize,
+    shard_height: usize,
+    hash: H256,
+    tx_num: usize,
+}
+
 pub struct Multichain {
     pub config: Configuration,
     order_chain: Blockchain,
     shard_chains: Vec<Blockchain>,
     confirmed_shard_blocks: Vec<Vec<H256>>,
-    longest_order_chain: Vec<H256>,
+    shard_states: Vec<UtxoState>,
+    //shard block -> the known order blocks confirming it
+    confirmers: HashMap<H256, Vec<H256>>,
+    //transaction -> the known blocks of its shard containing it, by shard
+    tx_blocks: Vec<HashMap<H256, Vec<H256>>>,
+    //order block of the longest order chain -> the receiver of its reward not paid yet
+    unclaimed_rewards: HashMap<H256, Address>,
+    //shard blocks placed in the global ledger by each order block of the longest order chain
+    ledger_blocks: Vec<Vec<LedgerBlock>>,
+    //transactions of the global ledger before each order block of the longest order chain, 
+    //the last one is the size of the ledger
+    ledger_offsets: Vec<usize>,
+    //shard block -> height of the order block placing it in the global ledger
+    ledger_heights: HashMap<H256, usize>,
+    //moves of the tip of the shard of this node, waiting to be followed by the mempool
+    reorgs: Vec<Reorg>,
 }
 
 // impl Clone for Multichain {
@@ -50,13 +100,226 @@ impl Multichain {
         shard_chains: Vec<Blockchain>, 
         config: &Configuration) -> Self 
     {   
+        let shard_num = shard_chains.len();
+        let shard_states: Vec<UtxoState> = (0..shard_num)
+            .map(|i| UtxoState::new(i, config))
+            .collect();
         
-        Multichain {
+        let mut multichain = Multichain {
             order_chain,
             shard_chains,
             confirmed_shard_blocks: vec![vec![]; config.shard_num],
-            longest_order_chain: vec![],
+            shard_states,
+            confirmers: HashMap::new(),
+            tx_blocks: vec![HashMap::new(); shard_num],
+            unclaimed_rewards: HashMap::new(),
+            ledger_blocks: vec![],
+            ledger_offsets: vec![0],
+            ledger_heights: HashMap::new(),
+            reorgs: vec![],
             config: config.clone(),
+        };
+        //the chains may be reopened with blocks from an earlier run
+        for hash in multichain.order_chain.all_blocks() {
+            multichain.index_order_block(&hash);
+        }
+        for shard_id in 0..shard_num {
+            for hash in multichain.shard_chains[shard_id].all_blocks() {
+                multichain.index_shard_block(shard_id, &hash);
+            }
+            multichain.update_shard_chain(shard_id);
+        }
+        for hash in multichain.order_chain.all_blocks_in_longest_chain() {
+            multichain.refresh_reward(&hash);
+            multichain.push_ledger_blocks(&hash);
+        }
+        multichain
+    }
+
+    //follow a move of the tip of the order chain
+    fn follow_order_reorg(&mut self, reorg: &Reorg) {
+        for _ in reorg.detached.iter() {
+            self.pop_ledger_blocks();
+        }
+        for hash in reorg.attached.iter() {
+            self.push_ledger_blocks(hash);
+        }
+        for hash in reorg.detached.iter().chain(reorg.attached.iter()) {
+            self.refresh_reward(hash);
+        }
+    }
+
+    //follow a move of the tip of a shard chain
+    fn follow_shard_reorg(&mut self, shard_id: usize, reorg: &Reorg) {
+        self.refresh_paid_rewards(shard_id, reorg);
+        //the ledger skips the shard blocks which are not on the longest chain of their shard
+        let mut lowest: Option<usize> = None;
+        for hash in reorg.detached.iter() {
+            if let Some(order_height) = self.ledger_heights.remove(hash) {
+                self.ledger_blocks[order_height].retain(|x| x.hash != *hash);
+                lowest = Some(lowest.map_or(order_height, |x| x.min(order_height)));
+            }
+        }
+        for hash in reorg.attached.iter() {
+            let order_height = match self.get_ledger_height(hash) {
+                Some(order_height) => order_height,
+                None => continue,
+            };
+            let ledger_block = match self.get_ledger_block(shard_id, hash) {
+                Some(ledger_block) => ledger_block,
+                None => continue,
+            };
+            let blocks = &mut self.ledger_blocks[order_height];
+            let index = blocks.partition_point(|x| *x < ledger_block);
+            blocks.insert(index, ledger_block);
+            self.ledger_heights.insert(*hash, order_height);
+            lowest = Some(lowest.map_or(order_height, |x| x.min(order_height)));
+        }
+        if let Some(lowest) = lowest {
+            for order_height in lowest..self.ledger_blocks.len() {
+                let tx_num: usize = self.ledger_blocks[order_height].iter().map(|x| x.tx_num).sum();
+                self.ledger_offsets[order_height + 1] = self.ledger_offsets[order_height] + tx_num;
+            }
+        }
+    }
+
+    //the height of the first order block of the longest order chain confirming a shard block
+    fn get_ledger_height(&self, shard_hash: &H256) -> Option<usize> {
+        self.confirmers
+            .get(shard_hash)?
+            .iter()
+            .filter(|x| self.order_chain.in_longest_chain(x))
+            .filter_map(|x| self.order_chain.get_block_height(x))
+            .min()
+    }
+
+    fn get_ledger_block(&self, shard_id: usize, hash: &H256) -> Option<LedgerBlock> {
+        let shard_chain = self.shard_chains.get(shard_id)?;
+        Some(LedgerBlock {
+            shard_id,
+            shard_height: shard_chain.get_block_height(hash)?,
+            hash: *hash,
+            tx_num: self.get_shard_block_by_shard(hash, shard_id)?.get_txs().len(),
+        })
+    }
+
+    //place the shard blocks a new tip of the order chain confirms in the global ledger,
+    //skipping those placed by an earlier order block
+    fn push_ledger_blocks(&mut self, order_hash: &H256) {
+        let order_height = self.ledger_blocks.len();
+        let mut blocks: Vec<LedgerBlock> = match self.get_order_block(order_hash) {
+            Some(order_block) => order_block
+                .get_confirmed_shard_blocks()
+                .iter()
+                .filter(|x| !self.ledger_heights.contains_key(x))
+                .filter_map(|x| {
+                    let shard_id = self.get_shard_id_of_block(x)?;
+                    if !self.shard_chains[shard_id].in_longest_chain(x) {
+                        return None;
+                    }
+                    self.get_ledger_block(shard_id, x)
+                })
+                .collect(),
+            None => vec![],
+        };
+        blocks.sort();
+        blocks.dedup();
+        for block in blocks.iter() {
+            self.ledger_heights.insert(block.hash, order_height);
+        }
+        let tx_num: usize = blocks.iter().map(|x| x.tx_num).sum();
+        self.ledger_offsets.push(self.ledger_offsets[order_height] + tx_num);
+        self.ledger_blocks.push(blocks);
+    }
+
+    //take the shard blocks placed by the tip of the order chain out of the global ledger
+    fn pop_ledger_blocks(&mut self) {
+        if let Some(blocks) = self.ledger_blocks.pop() {
+            for block in blocks {
+                self.ledger_heights.remove(&block.hash);
+            }
+            self.ledger_offsets.pop();
+        }
+    }
+
+    //track whether the reward of an order block is still to be paid: it is when the block 
+    //is on the longest order chain and no block of the shard of its miner pays it
+    fn refresh_reward(&mut self, order_hash: &H256) {
+        let receiver = match self.get_order_block(order_hash) {
+            Some(order_block) => order_block.get_coinbase_addr(),
+            None => return,
+        };
+        let shard_id = self.config.get_shard_id_by_addr(&receiver);
+        let unclaimed = self.config.order_reward > 0
+            && receiver != Address::default()
+            && self.order_chain.in_longest_chain(order_hash)
+            && !self.get_shard_state(shard_id).contains_tx(
+                &Transaction::create_coinbase_tx(*order_hash, &receiver, self.config.order_reward).hash()
+            );
+        if unclaimed {
+            self.unclaimed_rewards.insert(*order_hash, receiver);
+        } else {
+            self.unclaimed_rewards.remove(order_hash);
+        }
+    }
+
+    //the rewards paid by the blocks leaving or joining the longest chain of a shard
+    fn refresh_paid_rewards(&mut self, shard_id: usize, reorg: &Reorg) {
+        let rewarded: Vec<H256> = reorg.detached
+            .iter()
+            .chain(reorg.attached.iter())
+            .filter_map(|x| self.get_shard_block_by_shard(x, shard_id))
+            .flat_map(|x| x.get_txs())
+            .filter(|x| matches!(x.flag, TxFlag::Coinbase))
+            .filter_map(|x| x.inputs.first().map(|input| input.tx_hash))
+            .collect();
+        for order_hash in rewarded.iter() {
+            self.refresh_reward(order_hash);
+        }
+    }
+
+    //remember which shard blocks an order block confirms
+    fn index_order_block(&mut self, hash: &H256) {
+        let order_block = match self.get_order_block(hash) {
+            Some(order_block) => order_block,
+            None => return,
+        };
+        for shard_hash in order_block.get_confirmed_shard_blocks() {
+            self.confirmers.entry(shard_hash).or_default().push(*hash);
+        }
+    }
+
+    //remember which transactions a shard block contains
+    fn index_shard_block(&mut self, shard_id: usize, hash: &H256) {
+        let shard_block = match self.get_shard_block_by_shard(hash, shard_id) {
+            Some(shard_block) => shard_block,
+            None => return,
+        };
+        for tx in shard_block.get_txs() {
+            self.tx_blocks[shard_id].entry(tx.hash()).or_default().push(*hash);
+        }
+    }
+
+    //follow the longest chain of a shard, rolling back abandoned blocks
+    fn update_shard_chain(&mut self, shard_id: usize) {
+        let longest_shard_chain = self.shard_chains
+            .get(shard_id)
+            .unwrap()
+            .all_blocks_in_longest_chain();
+        let n = longest_shard_chain.len().saturating_sub(self.config.k);
+        let confirmed_shard_blocks = longest_shard_chain[..n].to_vec();
+        if let Some(ele) = self.confirmed_shard_blocks
+            .get_mut(shard_id) {
+                *ele = confirmed_shard_blocks;
+        } else {
+            panic!("Overflow");
+        }
+        let shard_chain = self.shard_chains.get(shard_id).unwrap();
+        if let Err(e) = self.shard_states
+            .get_mut(shard_id)
+            .unwrap()
+            .sync(&longest_shard_chain, shard_chain) {
+            error!("Fail to update the state of shard {}: {}", shard_id, e);
         }
     }
 
@@ -71,7 +334,10 @@ impl Multichain {
                 match self.order_chain
                     .insert_block_with_parent(block.clone(), &h) {
                     Ok(_) => {
-                        self.longest_order_chain = self.order_chain.all_blocks_in_longest_chain();
+                        self.index_order_block(&block.hash());
+                        if let Some(reorg) = self.order_chain.take_reorg() {
+                            self.follow_order_reorg(&reorg);
+                        }
                         Ok(true)
                     }
                     Err(e) => Err(e),
@@ -79,36 +345,78 @@ impl Multichain {
             }
             VersaHash::ShardHash(h) => {
                 let shard_id = block.get_shard_id();
-                let insert_success = match self.shard_chains
-                    .get_mut(shard_id)        
-                    .unwrap()
+                let shard_chain = self.shard_chains
+                    .get_mut(shard_id)
+                    .unwrap();
+                let insert_success = match shard_chain
                     .insert_block_with_parent(block.clone(), &h) {
                     Ok(_) => Ok(true),
                     Err(e) => Err(e),
                 };
+                let reorg = shard_chain.take_reorg();
                 match insert_success {
                     Ok(_) => {
-                        let longest_shard_chain = self.shard_chains
-                            .get(shard_id)
-                            .unwrap()
-                            .all_blocks_in_longest_chain();
-                        let n = longest_shard_chain.len().saturating_sub(self.config.k);
-                        let confirmed_shard_blocks = longest_shard_chain[..n].to_vec();
-                        if let Some(ele) = self.confirmed_shard_blocks
-                            .get_mut(shard_id) {
-                                *ele = confirmed_shard_blocks;
-                        } else {
-                            panic!("Overflow");
-                        }
-
+                        self.index_shard_block(shard_id, &block.hash());
+                        self.update_shard_chain(shard_id);
                     }
                     Err(_) => {}
                 }
+                if let Some(reorg) = reorg {
+                    self.follow_shard_reorg(shard_id, &reorg);
+                    if shard_id == self.config.shard_id {
+                        self.reorgs.push(reorg);
+                    }
+                }
                 insert_success
             }
         }
     }
 
+    /// Take the moves of the tip of this node's shard since the last call
+    pub fn take_reorgs(&mut self) -> Vec<Reorg> {
+        std::mem::take(&mut self.reorgs)
+    }
+
+    //the transactions of the blocks, in chain order
+    fn get_blocks_txs(&self, shard_id: usize, hashes: &[H256]) -> Vec<Transaction> {
+        hashes
+            .iter()
+            .filter_map(|x| self.get_shard_block_by_shard(x, shard_id))
+            .flat_map(|x| x.get_txs())
+            .collect()
+    }
+
+    /// Bring the mempool in line with the moves of the tip of this node's shard: 
+    /// the transactions of abandoned blocks come back, confirmed ones leave
+    pub fn reconcile_mempool(&mut self, mempool: &mut Mempool) {
+        let shard_id = self.config.shard_id;
+        for reorg in self.take_reorgs() {
+            let detached: Vec<H256> = reorg.detached.iter().rev().cloned().collect();
+            mempool.reconcile(
+                &self.get_blocks_txs(shard_id, &detached),
+                &self.get_blocks_txs(shard_id, &reorg.attached),
+                self.get_shard_state(shard_id),
+            );
+        }
+    }
+
+    /// Insert a shard block mined by this node and bring the mempool in line. If a peer block 
+    /// moved the tip while it was mined, the block is rejected or lands on a side fork, and 
+    /// no reorg brings its transactions back: they are put back in the mempool here.
+    pub fn insert_mined_shard_block(&mut self, shard_block: ShardBlock, mempool: &mut Mempool) 
+        -> Result<bool, String> 
+    {
+        let shard_id = shard_block.get_shard_id();
+        let hash = shard_block.hash();
+        let txs = shard_block.get_txs();
+        let parent = VersaHash::ShardHash(shard_block.get_shard_parent());
+        let result = self.insert_block_with_parent(VersaBlock::ShardBlock(shard_block), &parent);
+        self.reconcile_mempool(mempool);
+        if result.is_err() || !self.shard_chains[shard_id].in_longest_chain(&hash) {
+            mempool.restore_txs(&txs, self.get_shard_state(shard_id));
+        }
+        result
+    }
 
     pub fn all_blocks_in_longest_order_chain(&self) -> Vec<H256> {
         self.order_chain
@@ -143,6 +451,68 @@ impl Multichain {
             .tip()
     }
 
+    /// The target of an order block extending `parent`
+    pub fn get_next_order_target(&self, parent: &H256) -> Option<H256> {
+        self.order_chain
+            .next_target(parent)
+    }
+
+    /// The target of a shard block extending `parent` in the shard
+    pub fn get_next_shard_target(&self, shard_id: usize, parent: &H256) -> Option<H256> {
+        self.shard_chains
+            .get(shard_id)?
+            .next_target(parent)
+    }
+
+    /// The median time past of an order block extending `parent`
+    pub fn get_order_median_time_past(&self, parent: &H256) -> Option<SystemTime> {
+        self.order_chain
+            .get_median_time_past(parent)
+    }
+
+    /// The median time past of a shard block extending `parent` in the shard
+    pub fn get_shard_median_time_past(&self, shard_id: usize, parent: &H256) -> Option<SystemTime> {
+        self.shard_chains
+            .get(shard_id)?
+            .get_median_time_past(parent)
+    }
+
+    /// The median time past a block must exceed, none if its parent is not received yet
+    pub fn get_block_median_time_past(&self, block: &VersaBlock) -> Option<SystemTime> {
+        match block {
+            VersaBlock::OrderBlock(order_block) => {
+                self.get_order_median_time_past(&order_block.get_order_parent())
+            }
+            VersaBlock::ShardBlock(shard_block) => {
+                self.get_shard_median_time_past(
+                    shard_block.get_shard_id(),
+                    &shard_block.get_shard_parent(),
+                )
+            }
+        }
+    }
+
+    /// The target the chain expects a block to commit, none if it depends on a parent not received yet
+    pub fn get_block_target(&self, block: &VersaBlock) -> Option<H256> {
+        if self.config.retarget_window == 0 {
+            return match block {
+                VersaBlock::OrderBlock(_) => Some(self.config.order_diff),
+                VersaBlock::ShardBlock(_) => Some(self.config.block_diff),
+            };
+        }
+        match block {
+            VersaBlock::OrderBlock(order_block) => {
+                self.get_next_order_target(&order_block.get_order_parent())
+            }
+            VersaBlock::ShardBlock(shard_block) => {
+                self.get_next_shard_target(
+                    shard_block.get_shard_id(),
+                    &shard_block.get_shard_parent(),
+                )
+            }
+        }
+    }
+
     pub fn get_all_highest_shard_blocks(&self) -> Vec<(H256, usize)> {
         (0..self.config.shard_num)
             .into_iter()
@@ -153,13 +523,74 @@ impl Multichain {
     pub fn get_new_confirmed_shard_blocks(&self) -> Vec<H256> {
         let all_confirmed_shard_blocks = self.confirmed_shard_blocks.concat();
         
+        //skip those already confirmed by the longest order chain
+        let order_tip = self.order_chain.tip();
         let new_confirmed_shard_blocks: Vec<H256> = all_confirmed_shard_blocks
             .into_iter()
-            .filter(|x| !self.longest_order_chain.contains(x))
+            .filter(|x| !self.is_shard_block_anchored(x, &order_tip))
             .collect();
         new_confirmed_shard_blocks
     }
 
+    /// The shard of a known shard block
+    pub fn get_shard_id_of_block(&self, hash: &H256) -> Option<usize> {
+        self.shard_chains
+            .iter()
+            .position(|x| x.get_block_height(hash).is_some())
+    }
+
+    /// The confirmed shard blocks which are not received yet
+    pub fn get_missing_shard_blocks(&self, confirmed_shard_blocks: &[H256]) -> Vec<H256> {
+        confirmed_shard_blocks
+            .iter()
+            .filter(|x| self.get_shard_id_of_block(x).is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// The known confirmed shard blocks with less than k blocks on top of them yet
+    pub fn get_shallow_shard_blocks(&self, confirmed_shard_blocks: &[H256]) -> Vec<H256> {
+        confirmed_shard_blocks
+            .iter()
+            .filter(|x| match self.get_shard_id_of_block(x) {
+                Some(shard_id) => !self.shard_chains[shard_id].is_k_deep(x),
+                None => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Check the shard blocks confirmed by an order block extending `parent`: each must be 
+    /// known, have k blocks on top of it and not be confirmed by `parent` or its ancestors
+    pub fn validate_confirmed_shard_blocks(
+        &self,
+        parent: &H256,
+        confirmed_shard_blocks: &[H256],
+    ) -> Result<bool, String> {
+        if self.get_order_block(parent).is_none() {
+            return Err(String::from("Parent doesn't exisit"));
+        }
+        let mut confirmed: HashSet<H256> = HashSet::new();
+        for hash in confirmed_shard_blocks.iter() {
+            let shard_id = match self.get_shard_id_of_block(hash) {
+                Some(shard_id) => shard_id,
+                None => return Err(format!("Unknown shard block {:?}", hash)),
+            };
+            if !self.shard_chains[shard_id].is_k_deep(hash) {
+                return Err(format!(
+                    "Shard block {:?} is not {}-deep in shard {}", 
+                    hash, 
+                    self.config.k,
+                    shard_id
+                ));
+            }
+            if self.is_shard_block_anchored(hash, parent) || !confirmed.insert(*hash) {
+                return Err(format!("Shard block {:?} is already confirmed", hash));
+            }
+        }
+        Ok(true)
+    }
+
     
     pub fn get_order_block(&self, hash: &H256) -> Option<OrderBlock> {
         match self.order_chain.get_block(hash) {
@@ -188,6 +619,270 @@ impl Multichain {
         } 
     }
 
+    pub fn get_shard_state(&self, shard_id: usize) -> &UtxoState {
+        self.shard_states
+            .get(shard_id)
+            .unwrap()
+    }
+
+    /// Whether the shard block is confirmed by `order_parent` or one of its ancestors
+    pub fn is_shard_block_anchored(&self, shard_hash: &H256, order_parent: &H256) -> bool {
+        match self.confirmers.get(shard_hash) {
+            Some(confirmers) => confirmers
+                .iter()
+                .any(|x| self.order_chain.is_ancestor(x, order_parent)),
+            None => false,
+        }
+    }
+
+    /// Whether the transaction is in a shard block confirmed by `order_parent` or one of its ancestors
+    pub fn is_tx_anchored(&self, shard_id: usize, tx_hash: &H256, order_parent: &H256) -> bool {
+        match self.tx_blocks.get(shard_id).and_then(|x| x.get(tx_hash)) {
+            Some(shard_hashes) => shard_hashes
+                .iter()
+                .any(|x| self.is_shard_block_anchored(x, order_parent)),
+            None => false,
+        }
+    }
+
+    /// Check that every step of a cross-shard transfer in a shard block follows a step of 
+    /// the other shard which is anchored by the order parent of the block, and that every 
+    /// order block reward follows the order parent or one of its ancestors
+    pub fn check_anchors(
+        &self,
+        shard_id: usize,
+        parent: &H256,
+        order_parent: &H256,
+        txs: &[Transaction],
+    ) -> Result<bool, String> {
+        for (index, tx) in txs.iter().enumerate() {
+            if let TxFlag::Coinbase = tx.flag {
+                let rewarded = tx.inputs
+                    .first()
+                    .map(|x| x.tx_hash)
+                    .unwrap_or_default();
+                //the reward of the shard block itself comes first and refers to its parent
+                if index == 0 && rewarded == *parent {
+                    continue;
+                }
+                match self.get_order_block(&rewarded) {
+                    Some(order_block) if self.order_chain.is_ancestor(&rewarded, order_parent)
+                        && order_block.get_coinbase_addr() == tx.get_receiver_addr() => continue,
+                    _ => {
+                        return Err(format!(
+                            "Transaction {:?} rewards {:?} which is not an ancestor of the order parent",
+                            tx.hash(),
+                            rewarded
+                        ));
+                    }
+                }
+            }
+            let source = self.config.get_shard_id_by_addr(&tx.get_sender_addr());
+            let destination = self.config.get_shard_id_by_addr(&tx.get_receiver_addr());
+            let (anchor_shard, anchor_hash) = match tx.flag {
+                //the destination decides once the inputs are locked
+                TxFlag::Accept => (source, tx.get_related_hash(TxFlag::Input)),
+                TxFlag::Reject if source != shard_id => (source, tx.get_related_hash(TxFlag::Input)),
+                //the source finishes once the destination decides
+                TxFlag::Output => (destination, tx.get_related_hash(TxFlag::Accept)),
+                TxFlag::Reject => (destination, tx.hash()),
+                _ => continue,
+            };
+            if !self.is_tx_anchored(anchor_shard, &anchor_hash, order_parent) {
+                return Err(format!(
+                    "Transaction {:?} follows {:?} which is not anchored in shard {}",
+                    tx.hash(),
+                    anchor_hash,
+                    anchor_shard
+                ));
+            }
+        }
+        Ok(true)
+    }
+
+    /// The steps of cross-shard transfers this shard has to take next, deciding on incoming 
+    /// transfers and finishing outgoing ones, as anchored by the tip of the order chain
+    pub fn get_settlement_txs(&self, shard_id: usize) -> Vec<Transaction> {
+        let state = self.get_shard_state(shard_id);
+        let order_tip = self.order_chain.tip();
+        let mut txs: Vec<Transaction> = vec![];
+        for (source, source_state) in self.shard_states.iter().enumerate() {
+            if source == shard_id {
+                continue;
+            }
+            for lock in source_state.get_locks() {
+                let mut tx = lock.input_tx;
+                let input_hash = tx.hash();
+                if self.config.get_shard_id_by_addr(&tx.get_receiver_addr()) != shard_id 
+                    || state.is_settled(&input_hash)
+                    || !self.is_tx_anchored(source, &input_hash, &order_tip) {
+                    continue;
+                }
+                tx.flag = if state.can_accept(&tx) {
+                    TxFlag::Accept
+                } else {
+                    TxFlag::Reject
+                };
+                txs.push(tx);
+            }
+        }
+        for lock in state.get_locks() {
+            let mut tx = lock.input_tx;
+            let destination = self.config.get_shard_id_by_addr(&tx.get_receiver_addr());
+            if self.is_tx_anchored(destination, &tx.get_related_hash(TxFlag::Accept), &order_tip) {
+                tx.flag = TxFlag::Output;
+            } else if self.is_tx_anchored(destination, &tx.get_related_hash(TxFlag::Reject), &order_tip) {
+                tx.flag = TxFlag::Reject;
+            } else {
+                continue;
+            }
+            txs.push(tx);
+        }
+        //rewards of the order blocks mined to an address of this shard, in chain order
+        let mut rewards: Vec<(usize, H256, Address)> = self.unclaimed_rewards
+            .iter()
+            .filter(|(_, receiver)| self.config.get_shard_id_by_addr(receiver) == shard_id)
+            .map(|(hash, receiver)| {
+                (self.order_chain.get_block_height(hash).unwrap_or(0), *hash, *receiver)
+            })
+            .collect();
+        rewards.sort_by_key(|(height, hash, _)| (*height, *hash));
+        for (_, order_hash, receiver) in rewards {
+            txs.push(Transaction::create_coinbase_tx(
+                order_hash, 
+                &receiver, 
+                self.config.order_reward,
+            ));
+        }
+        txs
+    }
+
+    /// Check a transaction submitted by a user before it enters the mempool
+    pub fn validate_new_tx(&self, shard_id: usize, tx: &Transaction) -> Result<bool, String> {
+        self.validate_new_tx_with_ancestors(shard_id, tx, &[])
+    }
+
+    /// Check a transaction spending the outputs of pending `ancestors`, parents before children
+    pub fn validate_new_tx_with_ancestors(
+        &self,
+        shard_id: usize,
+        tx: &Transaction,
+        ancestors: &[Transaction],
+    ) -> Result<bool, String> {
+        if tx.flag.is_generated() {
+            return Err(format!(
+                "Transaction {:?} with flag {} can only be created by miners",
+                tx.hash(),
+                tx.flag.to_string()
+            ));
+        }
+        self.get_shard_state(shard_id).validate_tx_with_ancestors(tx, ancestors)
+    }
+
+    /// Validate a new transaction of this node's shard on top of the pending ones it spends, 
+    /// and insert it in the mempool with its fee. Both stay locked from the validation to 
+    /// the insertion, so that no block confirms or double spends the transaction in between.
+    pub fn admit_tx(&self, mempool: &mut Mempool, tx: Transaction) -> Result<bool, String> {
+        let shard_id = self.config.shard_id;
+        //the transaction may spend the outputs of pending ones
+        let ancestors = mempool.get_ancestor_txs(&tx);
+        if let Err(e) = self.validate_new_tx_with_ancestors(shard_id, &tx, &ancestors) {
+            mempool.record_invalid();
+            return Err(e);
+        }
+        let fee = self
+            .get_shard_state(shard_id)
+            .get_fee_with_ancestors(&tx, &ancestors)
+            .unwrap_or(0);
+        mempool.insert_tx(tx, fee)
+    }
+
+    /// Validate the transactions of a shard block against the state of its parent, 
+    /// the cross-shard steps against the order chain ending with its order parent
+    pub fn validate_shard_txs_with_parent(
+        &self,
+        shard_id: usize,
+        parent: &H256,
+        order_parent: &H256,
+        txs: &[Transaction],
+    ) -> Result<bool, String> {
+        if self.get_order_block(order_parent).is_none() {
+            return Err(String::from("Order parent doesn't exisit"));
+        }
+        self.check_anchors(shard_id, parent, order_parent, txs)?;
+        let state = self.shard_states
+            .get(shard_id)
+            .unwrap();
+        if state.tip().as_ref() == Some(parent) {
+            return state.validate_txs(txs);
+        }
+        //the block is on a fork, walk the state over to its parent without touching it
+        let shard_chain = self.shard_chains
+            .get(shard_id)
+            .unwrap();
+        let path = match shard_chain.all_blocks_end_with_block(parent) {
+            Some(path) => path,
+            None => return Err(String::from("Parent doesn't exisit")),
+        };
+        state.validate_txs_on_chain(txs, &path, shard_chain)
+    }
+
+    /// The total order of all the transactions, following the longest order chain.
+    /// Each order block places the shard blocks it confirms first, by shard id then height, 
+    /// skipping those confirmed by an earlier order block and those which left the 
+    /// longest chain of their shard.
+    pub fn get_global_ledger(&self) -> Vec<LedgerEntry> {
+        self.get_global_ledger_page(0, self.get_global_ledger_size())
+    }
+
+    /// The number of transactions in the global ledger
+    pub fn get_global_ledger_size(&self) -> usize {
+        self.ledger_offsets.last().cloned().unwrap_or(0)
+    }
+
+    /// A page of the global ledger, starting from the `start`-th transaction
+    pub fn get_global_ledger_page(&self, start: usize, limit: usize) -> Vec<LedgerEntry> {
+        let mut ledger: Vec<LedgerEntry> = vec![];
+        //the order block placing the `start`-th transaction
+        let first = self.ledger_offsets
+            .partition_point(|x| *x <= start)
+            .saturating_sub(1);
+        let mut skipped = start.saturating_sub(self.ledger_offsets[first]);
+        for (order_height, blocks) in self.ledger_blocks.iter().enumerate().skip(first) {
+            for block in blocks.iter() {
+                if ledger.len() >= limit {
+                    return ledger;
+                }
+                if skipped >= block.tx_num {
+                    skipped -= block.tx_num;
+                    continue;
+                }
+                let shard_block = self.get_shard_block_by_shard(&block.hash, block.shard_id).unwrap();
+                let txs = shard_block
+                    .get_txs()
+                    .into_iter()
+                    .enumerate()
+                    .skip(skipped)
+                    .take(limit - ledger.len());
+                for (tx_index, tx) in txs {
+                    ledger.push(LedgerEntry {
+                        position: LedgerPosition {
+                            order_height,
+                            shard_id: block.shard_id,
+                            shard_height: block.shard_height,
+                            tx_index,
+                        },
+                        shard_block: block.hash,
+                        tx_hash: tx.hash(),
+                        tx,
+                    });
+                }
+                skipped = 0;
+            }
+        }
+        ledger
+    }
+
     pub fn get_order_size(&self) -> usize {
         self.order_chain.size()
     }
@@ -3,6 +3,7 @@ use serde::{Serialize, Deserialize};
 use crate::{
         types::{
         hash::H256, 
+        encoding::{Encodable, Decodable, Reader},
     },
     sharding_bitcoin::{
         block::{
@@ -11,6 +12,7 @@ use crate::{
                 VersaHash,
             }
         },
+        transaction::Transaction,
     }
 };
 
@@ -21,8 +23,81 @@ pub enum Message {
     //Versa Block
     NewBlockHash(Vec<VersaHash>),
     GetBlocks(Vec<VersaHash>),
+    //ask for the tips of every chain, answered by NewBlockHash
+    GetTips,
     Blocks(Vec<VersaBlock>),
+    //blocks failing validation, with the reason of rejection
+    RejectedBlocks(Vec<(VersaHash, String)>),
+    //transactions, relayed to the peers of the shard owning the sender address
+    NewTransactionHashes(Vec<H256>),
+    GetTransactions(Vec<H256>),
+    Transactions(Vec<Transaction>),
     //key: block_hash, tx_index, value: (sample_index, sample) 
     //missing block
     // NewMissBlockHash((Vec<H256>, u32)),
 }
+
+//the tag of a variant is its position, new variants are appended
+impl Encodable for Message {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Message::Ping(nonce) => {
+                0u8.encode(out);
+                nonce.encode(out);
+            }
+            Message::Pong(nonce) => {
+                1u8.encode(out);
+                nonce.encode(out);
+            }
+            Message::NewBlockHash(hashes) => {
+                2u8.encode(out);
+                hashes.encode(out);
+            }
+            Message::GetBlocks(hashes) => {
+                3u8.encode(out);
+                hashes.encode(out);
+            }
+            Message::GetTips => {
+                4u8.encode(out);
+            }
+            Message::Blocks(blocks) => {
+                5u8.encode(out);
+                blocks.encode(out);
+            }
+            Message::RejectedBlocks(rejected) => {
+                6u8.encode(out);
+                rejected.encode(out);
+            }
+            Message::NewTransactionHashes(hashes) => {
+                7u8.encode(out);
+                hashes.encode(out);
+            }
+            Message::GetTransactions(hashes) => {
+                8u8.encode(out);
+                hashes.encode(out);
+            }
+            Message::Transactions(txs) => {
+                9u8.encode(out);
+                txs.encode(out);
+            }
+        }
+    }
+}
+
+impl Decodable for Message {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match u8::decode(reader)? {
+            0 => Ok(Message::Ping(String::decode(reader)?)),
+            1 => Ok(Message::Pong(String::decode(reader)?)),
+            2 => Ok(Message::NewBlockHash(Vec::<VersaHash>::decode(reader)?)),
+            3 => Ok(Message::GetBlocks(Vec::<VersaHash>::decode(reader)?)),
+            4 => Ok(Message::GetTips),
+            5 => Ok(Message::Blocks(Vec::<VersaBlock>::decode(reader)?)),
+            6 => Ok(Message::RejectedBlocks(Vec::<(VersaHash, String)>::decode(reader)?)),
+            7 => Ok(Message::NewTransactionHashes(Vec::<H256>::decode(reader)?)),
+            8 => Ok(Message::GetTransactions(Vec::<H256>::decode(reader)?)),
+            9 => Ok(Message::Transactions(Vec::<Transaction>::decode(reader)?)),
+            x => Err(format!("Unknown message {}", x)),
+        }
+    }
+}
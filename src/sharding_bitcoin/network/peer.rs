@@ -1,4 +1,5 @@
 use super::message::Message;
+use crate::types::encoding;
 use futures::{channel::mpsc, sink::SinkExt};
 use log::trace;
 use smol::Async;
@@ -34,7 +35,7 @@ pub struct TestReceiver {
 
 impl Handle {
     pub fn write(&mut self, msg: Message) {
-        let buffer = bincode::serialize(&msg).unwrap();
+        let buffer = encoding::serialize(&msg);
         smol::block_on(async move {
             if self.write_queue.send(buffer).await.is_err() {
                 trace!("Trying to send to disconnected peer");
@@ -65,7 +66,7 @@ impl Handle {
 impl TestReceiver {
     pub fn recv(&mut self) -> Message {
         let bytes = smol::block_on(futures::stream::StreamExt::next(&mut self.r)).unwrap();
-        let msg: Message = bincode::deserialize(&bytes).unwrap();
+        let msg: Message = encoding::deserialize(&bytes).unwrap();
         msg
     }
 }
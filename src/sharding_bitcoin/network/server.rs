@@ -1,5 +1,8 @@
 use crate::{
-    types::address::Address,
+    types::{
+        address::Address,
+        hash::H256,
+    },
     sharding_bitcoin::network::{
         peer,
         message::Message,
@@ -21,7 +24,7 @@ use futures::{
     channel::oneshot, stream::StreamExt
 };
 use smol::{Async, Executor};
-use log::{info, trace};
+use log::{info, trace, warn};
 use std::{
     net,
     sync::Arc,
@@ -29,10 +32,14 @@ use std::{
 };
 
 
+//shard id and genesis hash exchanged when peers connect
+const HANDSHAKE_SIZE: usize = 4 + 32;
+
 pub fn new(
     addr: std::net::SocketAddr,
     msg_sink: smol::channel::Sender<(Vec<u8>, peer::Handle)>,
     shard_id: usize,
+    genesis_hash: H256,
 ) -> std::io::Result<(Context, Handle)> {
     let (control_signal_sender, control_signal_receiver) = smol::channel::bounded(10000);
     let handle = Handle {
@@ -46,6 +53,7 @@ pub fn new(
         control_sender: control_signal_sender,
         new_msg_chan: msg_sink,
         shard_id,
+        genesis_hash,
     };
     Ok((ctx, handle))
 }
@@ -58,6 +66,7 @@ pub struct Context {
     control_sender: smol::channel::Sender<ControlSignal>,
     new_msg_chan: smol::channel::Sender<(Vec<u8>, peer::Handle)>,
     shard_id: usize,
+    genesis_hash: H256, //peers must build the same genesis blocks
 }
 
 impl Context {
@@ -140,8 +149,9 @@ impl Context {
                     match self.peers_by_shard.get_mut(&shard_id) {
                         Some(peers) => {
                             for peer in peers.iter_mut() {
-                            let hd = self.peers.get_mut(peer).unwrap();
-                                hd.write(msg.clone());
+                                if let Some(hd) = self.peers.get_mut(peer) {
+                                    hd.write(msg.clone());
+                                }
                             }
                         }
                         None => {}
@@ -155,6 +165,9 @@ impl Context {
                 ControlSignal::DroppedPeer(addr) => {
                     trace!("Processing DroppedPeer({})", addr);
                     self.peers.remove(&addr);
+                    for peers in self.peers_by_shard.values_mut() {
+                        peers.retain(|x| x != &addr);
+                    }
                     info!("Peer {} disconnected", addr);
                 }
                 ControlSignal::SendToPeer((_receiver, _msg)) => {
@@ -174,18 +187,17 @@ impl Context {
         info!("Establishing connection to peer {}", addr);
         let mut stream = Async::<std::net::TcpStream>::connect(addr.clone()).await?;
         
-        //send the shard id to the outgoing peer
-        let shard_id = self.shard_id as u32;
-        let serialized_shard_id = shard_id.to_be_bytes();
-        stream.write_all(&serialized_shard_id).await?; 
-        //receive the shard id from incoming peer
-        let mut buffer = [0u8; std::mem::size_of::<u32>()];
+        //send the shard id and the genesis hash to the outgoing peer
+        stream.write_all(&self.handshake()).await?; 
+        //receive them from incoming peer
+        let mut buffer = [0u8; HANDSHAKE_SIZE];
         let _ = stream.read_exact(&mut buffer).await?;
-        let received_shard_id = u32::from_be_bytes(buffer);
+        let received_shard_id = self.check_handshake(&buffer)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
         info!("Connecting node's shard id: {}", received_shard_id);
 
         // register the new peer
-        self.register(stream, peer::Direction::Outgoing, ex, received_shard_id as usize).await
+        self.register(stream, peer::Direction::Outgoing, ex, received_shard_id).await
     }
 
     async fn accept(
@@ -193,20 +205,45 @@ impl Context {
         mut stream: Async<net::TcpStream>,
         ex: Arc<Executor<'_>>,
     ) -> std::io::Result<()> {
-        //receive the shard id from incoming peer
-        let mut buffer = [0u8; std::mem::size_of::<u32>()];
+        //receive the shard id and the genesis hash from incoming peer
+        let mut buffer = [0u8; HANDSHAKE_SIZE];
         let _ = stream.read_exact(&mut buffer).await?;
-        let received_shard_id = u32::from_be_bytes(buffer);
-        //send the shard id to the outgoing peer
-        let shard_id = self.shard_id as u32;
-        let serialized_shard_id = shard_id.to_be_bytes();
-        stream.write_all(&serialized_shard_id).await?; 
+        //send them to the outgoing peer, so that it can refuse us as well
+        stream.write_all(&self.handshake()).await?; 
+        let received_shard_id = match self.check_handshake(&buffer) {
+            Ok(shard_id) => shard_id,
+            Err(e) => {
+                warn!("Refusing incoming peer: {}", e);
+                return Ok(());
+            }
+        };
         info!("Coming node's shard id: {}", received_shard_id);
 
-        self.register(stream, peer::Direction::Incoming, ex, received_shard_id as usize).await?;
+        self.register(stream, peer::Direction::Incoming, ex, received_shard_id).await?;
         Ok(())
     }
 
+    fn handshake(&self) -> [u8; HANDSHAKE_SIZE] {
+        let mut buffer = [0u8; HANDSHAKE_SIZE];
+        buffer[..4].copy_from_slice(&(self.shard_id as u32).to_be_bytes());
+        buffer[4..].copy_from_slice(self.genesis_hash.as_ref());
+        buffer
+    }
+
+    //the shard id of the peer, if it builds the same genesis blocks
+    fn check_handshake(&self, buffer: &[u8; HANDSHAKE_SIZE]) -> Result<usize, String> {
+        let shard_id = u32::from_be_bytes(buffer[..4].try_into().unwrap());
+        let genesis_hash: H256 = <[u8; 32]>::try_from(&buffer[4..]).unwrap().into();
+        if genesis_hash != self.genesis_hash {
+            return Err(format!(
+                "genesis hash {} differs from ours {}",
+                genesis_hash,
+                self.genesis_hash
+            ));
+        }
+        Ok(shard_id as usize)
+    }
+
     async fn register(
         &mut self,
         stream: Async<net::TcpStream>,
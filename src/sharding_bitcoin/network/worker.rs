@@ -1,6 +1,7 @@
 use crate::{
     types::{
         hash::{H256, Hashable},
+        encoding,
     },
     sharding_bitcoin::{
         network::{
@@ -8,6 +9,7 @@ use crate::{
             peer,
             server::Handle as ServerHandle,
         },
+        miner::Handle as MinerHandle,
         block::{
             Info, 
             versa_block::{
@@ -16,7 +18,8 @@ use crate::{
             }
         },
         configuration::Configuration,
-        // validator::{Validator},
+        transaction::Transaction,
+        validator::Validator,
         mempool::Mempool,
         multichain::Multichain,
     }
@@ -26,8 +29,14 @@ use std::{
     thread,
     sync::{Arc,Mutex},
     collections::{HashMap, VecDeque},
+    time::{Duration, SystemTime},
 };
 
+//blocks kept by a worker until they can be validated, of each kind, later ones are rejected
+const MAX_DEFERRED_BLOCKS: usize = 256;
+//how often the order blocks waiting for deeper shard blocks are retried
+const SHALLOW_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 //#[cfg(any(test,test_utilities))]
 //use super::peer::TestReceiver as PeerTestReceiver;
 //#[cfg(any(test,test_utilities))]
@@ -37,11 +46,16 @@ pub struct Worker {
     msg_chan: smol::channel::Receiver<(Vec<u8>, peer::Handle)>,
     num_worker: usize,
     server: ServerHandle,
+    miner: MinerHandle,
     multichain: Arc<Mutex<Multichain>>,
     mempool: Arc<Mutex<Mempool>>,
     config: Configuration,
-    // validator: Validator,
+    validator: Validator,
     blk_buff: HashMap<VersaHash, Vec<VersaBlock>>,
+    //blocks ahead of the local clock, with the peer which sent them
+    deferred: Vec<(VersaBlock, peer::Handle)>,
+    //order blocks confirming shard blocks without k blocks on top of them yet, with their peer
+    shallow: Vec<(VersaBlock, peer::Handle)>,
     unavailable_cmt2avai_blocks: HashMap<H256, Vec<VersaBlock>>, //cmt -> avai blocks containing cmt
     unavailable_avai_block2cmts: HashMap<H256, Vec<H256>> // avai block hash -> cmts
 }
@@ -54,6 +68,7 @@ impl Worker {
         num_worker: usize,
         msg_src: smol::channel::Receiver<(Vec<u8>, peer::Handle)>,
         server: &ServerHandle,
+        miner: &MinerHandle,
         multichain: &Arc<Mutex<Multichain>>,
         mempool: &Arc<Mutex<Mempool>>,
         config: &Configuration,
@@ -62,10 +77,14 @@ impl Worker {
             msg_chan: msg_src,
             num_worker,
             server: server.clone(),
+            miner: miner.clone(),
             multichain: Arc::clone(multichain),
             blk_buff: HashMap::new(),
+            deferred: vec![],
+            shallow: vec![],
             mempool: Arc::clone(mempool),
             config: config.clone(),
+            validator: Validator::new(config),
             unavailable_cmt2avai_blocks: HashMap::new(),
             unavailable_avai_block2cmts: HashMap::new(),
         }
@@ -86,14 +105,36 @@ impl Worker {
 
     fn worker_loop(&mut self) {
         loop {
-            let result = smol::block_on(self.msg_chan.recv());
-            if let Err(e) = result {
-                error!("network worker terminated {}", e);
-                break;
-            }
-            let msg = result.unwrap();
-            let (msg, mut peer) = msg;
-            let msg: Message = bincode::deserialize(&msg).unwrap();
+            let result = match self.next_retry() {
+                //wake up when a deferred block may be accepted
+                Some(wait) => smol::block_on(smol::future::or(
+                    async { self.msg_chan.recv().await.map(Some) },
+                    async {
+                        smol::Timer::after(wait).await;
+                        Ok(None)
+                    },
+                )),
+                None => smol::block_on(self.msg_chan.recv()).map(Some),
+            };
+            let (msg, mut peer) = match result {
+                Ok(Some(msg)) => msg,
+                Ok(None) => {
+                    self.retry_deferred();
+                    self.retry_shallow();
+                    continue;
+                }
+                Err(e) => {
+                    error!("network worker terminated {}", e);
+                    break;
+                }
+            };
+            let msg: Message = match encoding::deserialize(&msg) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("Dropping a malformed message: {}", e);
+                    continue;
+                }
+            };
             match msg {
                 Message::Ping(nonce) => {
                     debug!("Ping: {}", nonce);
@@ -111,6 +152,9 @@ impl Worker {
                         peer.write(response);
                     }
                 }
+                Message::GetTips => {
+                    peer.write(self.handle_get_tips());
+                }
                 Message::GetBlocks(hash_vec) => {
                     //debug!("Get versa blocks");
                     if let Some(response) = self
@@ -120,17 +164,31 @@ impl Worker {
                 }
                 Message::Blocks(blocks) => {
                     //debug!("Coming versa blocks");
-                    let (response_1, response_2) = self
-                        .handle_blocks(blocks); 
-                    if let Some(new_blks) = response_1 {
-                        self.server.broadcast(new_blks);
+                    self.receive_blocks(blocks, &mut peer);
+                }
+                Message::RejectedBlocks(rejected_blks) => {
+                    for (versa_hash, reason) in rejected_blks {
+                        warn!("Peer {} rejected block {:?}: {}", peer.addr(), versa_hash, reason);
                     }
-
-                    //handle missing blocks
-                    if let Some(missing_blks) = response_2 {
-                        peer.write(missing_blks);
+                }
+                Message::NewTransactionHashes(tx_hashs) => {
+                    if let Some(response) = self
+                        .handle_new_tx_hashes(tx_hashs) {
+                        peer.write(response);
+                    }
+                }
+                Message::GetTransactions(tx_hashs) => {
+                    if let Some(response) = self
+                        .handle_get_txs(tx_hashs) {
+                        peer.write(response);
+                    }
+                }
+                Message::Transactions(txs) => {
+                    //only the peers of this shard are told about new transactions
+                    if let Some(new_txs) = self.handle_txs(txs) {
+                        self.miner.update();
+                        self.server.broadcast_with_shard(new_txs, self.config.shard_id);
                     }
-
                 }
                 
             }
@@ -198,6 +256,18 @@ impl Worker {
         }
     }
 
+    fn handle_get_tips(&self) -> Message {
+        let multichain = self.multichain.lock().unwrap();
+        let mut tips: Vec<VersaHash> = vec![
+            VersaHash::OrderHash(multichain.get_highest_order_block())
+        ];
+        tips.extend(multichain
+            .get_all_highest_shard_blocks()
+            .into_iter()
+            .map(|(hash, _)| VersaHash::ShardHash(hash)));
+        Message::NewBlockHash(tips)
+    }
+
     fn handle_get_blocks(&self, hash_vec: Vec<VersaHash>) 
         -> Option<Message>
     {
@@ -250,25 +320,188 @@ impl Worker {
         }
     }
 
-    fn handle_blocks(&mut self, blocks: Vec<VersaBlock>) 
-        -> (Option<Message>, Option<Message>) 
-    //new_block_hash, missing block, missing symbols
+    fn handle_new_tx_hashes(&self, tx_hashs: Vec<H256>) -> Option<Message> {
+        if tx_hashs.is_empty() {
+            return None;
+        }
+        let mut unreceived_txs: Vec<H256> = vec![];
+        for tx_hash in tx_hashs {
+            if self.mempool.lock().unwrap().check(&tx_hash) {
+                continue;
+            }
+            if self.multichain
+                .lock()
+                .unwrap()
+                .get_shard_state(self.config.shard_id)
+                .contains_tx(&tx_hash) {
+                continue;
+            }
+            unreceived_txs.push(tx_hash);
+        }
+        if !unreceived_txs.is_empty() {
+            Some(Message::GetTransactions(unreceived_txs))
+        } else {
+            None
+        }
+    }
+
+    fn handle_get_txs(&self, tx_hashs: Vec<H256>) -> Option<Message> {
+        if tx_hashs.is_empty() {
+            return None;
+        }
+        let res_txs: Vec<Transaction> = tx_hashs
+            .iter()
+            .filter_map(|tx_hash| self.mempool.lock().unwrap().get_tx(tx_hash))
+            .collect();
+        if !res_txs.is_empty() {
+            Some(Message::Transactions(res_txs))
+        } else {
+            None
+        }
+    }
+
+    fn handle_txs(&self, txs: Vec<Transaction>) -> Option<Message> {
+        if txs.is_empty() {
+            return None;
+        }
+        let mut new_tx_hashs: Vec<H256> = vec![];
+        for tx in txs {
+            let tx_hash = tx.hash();
+            //transactions of other shards are not kept
+            let owner = self.config.get_shard_id_by_addr(&tx.get_sender_addr());
+            if owner != self.config.shard_id {
+                debug!("Ignore transaction {:?} of shard {}", tx_hash, owner);
+                continue;
+            }
+            let multichain = self.multichain.lock().unwrap();
+            let mut mempool = self.mempool.lock().unwrap();
+            if mempool.check(&tx_hash) {
+                continue;
+            }
+            match multichain.admit_tx(&mut mempool, tx) {
+                Ok(_) => new_tx_hashs.push(tx_hash),
+                Err(e) => debug!("Reject transaction {:?}: {}", tx_hash, e),
+            }
+        }
+        if !new_tx_hashs.is_empty() {
+            Some(Message::NewTransactionHashes(new_tx_hashs))
+        } else {
+            None
+        }
+    }
+
+    fn receive_blocks(&mut self, blocks: Vec<VersaBlock>, peer: &mut peer::Handle) {
+        let (response_1, response_2, response_3) = self
+            .handle_blocks(blocks, peer); 
+        let inserted = response_1.is_some();
+        if let Some(new_blks) = response_1 {
+            //the miner extends the new tips
+            self.miner.update();
+            self.server.broadcast(new_blks);
+        }
+
+        //handle missing blocks
+        if let Some(missing_blks) = response_2 {
+            peer.write(missing_blks);
+        }
+
+        //tell the sender which blocks are rejected and why
+        if let Some(rejected_blks) = response_3 {
+            peer.write(rejected_blks);
+        }
+
+        //the new blocks may deepen the shard blocks waiting order blocks confirm
+        if inserted {
+            self.retry_shallow();
+        }
+    }
+
+    //how long until a deferred block may be accepted: the earliest one ahead of the clock 
+    //is no longer, or the shard blocks are inserted by other workers in the meantime
+    fn next_retry(&self) -> Option<Duration> {
+        let latest = SystemTime::now() + Duration::from_millis(self.config.max_time_drift);
+        let ahead = self.deferred
+            .iter()
+            .map(|(block, _)| block
+                .get_timestamp()
+                .duration_since(latest)
+                .unwrap_or_default())
+            .min();
+        if self.shallow.is_empty() {
+            return ahead;
+        }
+        Some(ahead.map_or(SHALLOW_RETRY_INTERVAL, |x| x.min(SHALLOW_RETRY_INTERVAL)))
+    }
+
+    //validate again the deferred blocks the clock has caught up with
+    fn retry_deferred(&mut self) {
+        let deferred = std::mem::take(&mut self.deferred);
+        let (ready, waiting): (Vec<_>, Vec<_>) = deferred
+            .into_iter()
+            .partition(|(block, _)| self.validator.validate_time_drift(block).is_ok());
+        self.deferred = waiting;
+        for (block, mut peer) in ready {
+            info!("Retry deferred block {:?}", block.hash());
+            self.receive_blocks(vec![block], &mut peer);
+        }
+    }
+
+    //validate again the order blocks whose confirmed shard blocks have become k-deep
+    fn retry_shallow(&mut self) {
+        if self.shallow.is_empty() {
+            return;
+        }
+        let shallow = std::mem::take(&mut self.shallow);
+        let (ready, waiting): (Vec<_>, Vec<_>) = {
+            let multichain = self.multichain.lock().unwrap();
+            shallow
+                .into_iter()
+                .partition(|(block, _)| self.validator.get_shallow_refs(&multichain, block).is_empty())
+        };
+        self.shallow = waiting;
+        for (block, mut peer) in ready {
+            info!("Retry block {:?} with deep enough shard blocks", block.hash());
+            self.receive_blocks(vec![block], &mut peer);
+        }
+    }
+
+    fn handle_blocks(&mut self, blocks: Vec<VersaBlock>, peer: &peer::Handle) 
+        -> (Option<Message>, Option<Message>, Option<Message>) 
+    //new_block_hash, missing block, rejected blocks
     {
         if blocks.is_empty() {
-            return (None, None);
+            return (None, None, None);
         }
 
         
         let mut new_hashs: Vec<VersaHash> = vec![];
         let mut missing_parents: Vec<VersaHash> = vec![];
+        let mut rejected_blks: Vec<(VersaHash, String)> = vec![];
         
         // return tx
         for block in blocks {
             //verification
-            //verify if hash is valid
-            if !block.verify_hash() {
-                // return Err(String::from("Incorrect hash"));
-                info!("Reject block {:?} for incorrect hash", block.hash());
+            //verify the hash and the proof of work
+            if let Err(e) = self.validator.validate_block(&block) {
+                info!("Reject block {:?}: {}", block.hash(), e);
+                let versa_hash = block.get_versa_hash();
+                self.drop_buffered(&versa_hash);
+                rejected_blks.push((versa_hash, e));
+                continue;
+            }
+            //a block ahead of the local clock is kept until the clock catches up
+            if let Err(e) = self.validator.validate_time_drift(&block) {
+                if self.deferred.len() < MAX_DEFERRED_BLOCKS {
+                    info!("Defer block {:?}: {}", block.hash(), e);
+                    if !self.deferred.iter().any(|(x, _)| *x == block) {
+                        self.deferred.push((block, peer.clone()));
+                    }
+                    continue;
+                }
+                info!("Reject block {:?}: {}", block.hash(), e);
+                let versa_hash = block.get_versa_hash();
+                self.drop_buffered(&versa_hash);
+                rejected_blks.push((versa_hash, e));
                 continue;
             }
             let block_hash = block.hash();
@@ -276,9 +509,11 @@ impl Worker {
             
             // let shard_id = block.get_shard_id();
             //insert the block
-            let (sub_new_hashes, sub_missing_parents) = self.insert_block(block.clone());
+            let (sub_new_hashes, sub_missing_parents, sub_rejected_blks) = 
+                self.insert_block(block.clone(), peer);
             new_hashs.extend(sub_new_hashes);
             missing_parents.extend(sub_missing_parents);
+            rejected_blks.extend(sub_rejected_blks);
         }
 
 
@@ -291,15 +526,23 @@ impl Worker {
             true => None,
             false => Some(Message::GetBlocks(missing_parents)),
         };
+
+        let res_rejected_blks = match rejected_blks.is_empty() {
+            true => None,
+            false => Some(Message::RejectedBlocks(rejected_blks)),
+        };
         
 
-        (res_new_hashes, res_missing_blks)
+        (res_new_hashes, res_missing_blks, res_rejected_blks)
     }
 
-    fn insert_block(&mut self, block: VersaBlock) -> (Vec<VersaHash>, Vec<VersaHash>) {
+    fn insert_block(&mut self, block: VersaBlock, peer: &peer::Handle) 
+        -> (Vec<VersaHash>, Vec<VersaHash>, Vec<(VersaHash, String)>) 
+    {
         let mut new_hashs: Vec<VersaHash> = vec![];
         // let mut missing_parents: HashMap<usize, Vec<H256>> = HashMap::new();
         let mut missing_parents: Vec<VersaHash> = vec![];
+        let mut rejected_blks: Vec<(VersaHash, String)> = vec![];
         let parents: Vec<(VersaHash, usize)> = match block.clone() {
             VersaBlock::OrderBlock(order_block) => {
                 vec![(VersaHash::OrderHash(order_block.get_order_parent()), 0)]
@@ -350,18 +593,7 @@ impl Worker {
 
             //put the block in buff
             if parent_not_exisit {
-                match self.blk_buff.get(&parent_hash) {
-                    Some(old_blks) => {
-                        if !old_blks.contains(&block) {
-                            let mut new_blks = old_blks.clone();
-                            new_blks.push(block.clone());
-                            self.blk_buff.insert(parent_hash.clone(), new_blks);
-                        }
-                    }
-                    None => {
-                        self.blk_buff.insert(parent_hash.clone(), vec![block.clone()]);
-                    }
-                }
+                self.buffer_block(parent_hash.clone(), block.clone());
                 
                 info!("block {:?} insertion failure in shard {}: parent {:?} not fould", block.hash(), inserted_shard_id, parent_hash);
                 if !missing_parents.contains(&parent_hash) {
@@ -370,63 +602,110 @@ impl Worker {
                 continue;
             }                
 
-            // match self.validator.validate_block(&block) {
-            //     Ok(_) => {}
-            //     Err(e) => {
-            //         info!("block insertion failure: the verification fails: {:?}", e);
-            //         continue;
-            //     }
-            // }
-            
-
             let mut inserted_blks: VecDeque<VersaBlock> = VecDeque::new();
             inserted_blks.push_back(block.clone());
-            let mut removed_buff: Vec<VersaHash> = vec![];
-            while !inserted_blks.is_empty() {
-                let inserted_blk = inserted_blks.pop_front().unwrap();
-                match self.multichain
-                    .lock()
-                    .unwrap()
-                    .insert_block_with_parent(
+            while let Some(inserted_blk) = inserted_blks.pop_front() {
+                let (new_hash, inserted_parent) = match inserted_blk.clone() {
+                    VersaBlock::OrderBlock(order_block) => (
+                        VersaHash::OrderHash(inserted_blk.hash()),
+                        VersaHash::OrderHash(order_block.get_order_parent()),
+                    ),
+                    VersaBlock::ShardBlock(shard_block) => (
+                        VersaHash::ShardHash(inserted_blk.hash()),
+                        VersaHash::ShardHash(shard_block.get_shard_parent()),
+                    ),
+                };
+                //the block is verified and inserted under one lock, 
+                //so that the chain it extends cannot change in between
+                let mut multichain = self.multichain.lock().unwrap();
+                //an order block waits for the shard blocks it confirms, 
+                //a shard block for its order parent
+                let missing_refs = self.validator.get_missing_refs(&multichain, &inserted_blk);
+                if let Some(missing_ref) = missing_refs.first() {
+                    drop(multichain);
+                    info!("block {:?} waits for block {:?}", inserted_blk.hash(), missing_ref);
+                    self.buffer_block(missing_ref.clone(), inserted_blk.clone());
+                    for missing_hash in missing_refs {
+                        if !missing_parents.contains(&missing_hash) {
+                            missing_parents.push(missing_hash);
+                        }
+                    }
+                    continue;
+                }
+                //an order block waits until the shard blocks it confirms are k-deep, 
+                //as long as this worker has room for it
+                let shallow_refs = self.validator.get_shallow_refs(&multichain, &inserted_blk);
+                if let Some(shallow_ref) = shallow_refs.first() {
+                    if self.shallow.len() < MAX_DEFERRED_BLOCKS {
+                        drop(multichain);
+                        info!("block {:?} waits until shard block {:?} is {}-deep", inserted_blk.hash(), shallow_ref, self.config.k);
+                        if !self.shallow.iter().any(|(x, _)| *x == inserted_blk) {
+                            self.shallow.push((inserted_blk.clone(), peer.clone()));
+                        }
+                        continue;
+                    }
+                }
+                //verify the block against the chain it extends
+                if let Err(e) = self.validator.validate_with_parent(&multichain, &inserted_blk) {
+                    drop(multichain);
+                    info!("Reject block {:?} in shard {}: the verification fails: {}", inserted_blk.hash(), self.config.shard_id, e);
+                    self.drop_buffered(&new_hash);
+                    rejected_blks.push((new_hash, e));
+                    continue;
+                }
+                let insert_result = multichain.insert_block_with_parent(
                     inserted_blk.clone(),
-                    &parent_hash
-                ) {
+                    &inserted_parent
+                );
+                multichain.reconcile_mempool(&mut self.mempool.lock().unwrap());
+                drop(multichain);
+                match insert_result {
                     Ok(_) => {
-                        let new_hash = match inserted_blk.clone() {
-                            VersaBlock::OrderBlock(_) 
-                                => VersaHash::OrderHash(inserted_blk.hash()),
-                            VersaBlock::ShardBlock(_)
-                                => VersaHash::ShardHash(inserted_blk.hash()),
-                        };
                         new_hashs.push(new_hash.clone());
                         info!("successfully inserting block: {:?}", new_hash);
                         
 
                         //if there are some blocks in the buff whose parent is the new block,
-                        //continue to insert it
-                        match self.blk_buff.get(&new_hash) {
-                            Some(child_blks) => {
-                                for child_blk in child_blks {
-                                    inserted_blks.push_back(child_blk.clone());
-                                }
-                                removed_buff.push(new_hash);
-                            }
-                            None => {
-                                break;
-                            }
+                        //continue to insert them
+                        if let Some(child_blks) = self.blk_buff.remove(&new_hash) {
+                            inserted_blks.extend(child_blks);
                         }
                     }
                     Err(e) => {
                         info!("Reject block {:?} in shard {}: insertion fails: {}", inserted_blk.hash(), self.config.shard_id, e);
-                        break;
                     }
                 }
             }
-            for item2 in removed_buff {
-                self.blk_buff.remove(&item2);
+        }
+        (new_hashs, missing_parents, rejected_blks)
+    }
+
+    //forget the blocks waiting for a rejected block, and the blocks waiting for them, 
+    //they can never be inserted
+    fn drop_buffered(&mut self, rejected: &VersaHash) {
+        let mut dropped: Vec<VersaHash> = vec![rejected.clone()];
+        while let Some(hash) = dropped.pop() {
+            if let Some(blks) = self.blk_buff.remove(&hash) {
+                for blk in blks {
+                    info!("Drop block {:?} waiting for rejected block {:?}", blk.hash(), hash);
+                    dropped.push(blk.get_versa_hash());
+                }
+            }
+        }
+    }
+
+    //keep the block until the block it depends on arrives
+    fn buffer_block(&mut self, dependency: VersaHash, block: VersaBlock) {
+        match self.blk_buff.get_mut(&dependency) {
+            Some(old_blks) => {
+                if !old_blks.contains(&block) {
+                    old_blks.push(block);
+                }
+            }
+            None => {
+                self.blk_buff.insert(dependency, vec![block]);
             }
         }
-        (new_hashs, missing_parents)
     }
 }
 
@@ -0,0 +1,695 @@
+use crate::{
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+    },
+    sharding_bitcoin::{
+        block::{
+            Content,
+            versa_block::VersaBlock,
+        },
+        blockchain::Blockchain,
+        configuration::Configuration,
+        transaction::{Transaction, TxFlag, UtxoOutput},
+    },
+};
+use std::collections::{HashMap, HashSet};
+
+/// An unspent output is identified by the hash of its transaction and its index
+pub type Outpoint = (H256, u32);
+
+/// An unspent output, its owner signs the spending transaction with the output public key
+#[derive(Clone, Debug)]
+pub struct Utxo {
+    pub output: UtxoOutput,
+    pub height: usize, //height in the shard chain of the block creating the output
+    pub coinbase: bool, //a block reward, only spendable once it is mature
+}
+
+/// The outputs spent by the Input transaction of a cross-shard transfer, 
+/// held until the destination shard accepts or rejects the transfer
+#[derive(Clone, Debug)]
+pub struct Lock {
+    pub input_tx: Transaction,
+    pub spent: Vec<Utxo>,
+}
+
+//what a block changed, so that it can be reverted when the chain reorganizes
+#[derive(Clone, Default)]
+struct BlockUndo {
+    spent: Vec<(Outpoint, Utxo)>,
+    created: Vec<Outpoint>,
+    locked: Vec<H256>,
+    released: Vec<(H256, Lock)>,
+    settled: Vec<H256>,
+    txs: Vec<H256>,
+}
+
+//the changes made by the transactions checked so far in a block, over the state. 
+//on a fork, they also hold the blocks reverted and replayed to reach the block's parent
+#[derive(Default)]
+struct BlockView {
+    spent: HashSet<Outpoint>,
+    created: HashMap<Outpoint, Utxo>,
+    locked: HashMap<H256, Lock>,
+    released: HashSet<H256>,
+    settled: HashSet<H256>,
+    unsettled: HashSet<H256>,
+    txs: HashSet<H256>,
+    removed_txs: HashSet<H256>,
+    fees: u64,
+    height: usize, //number of blocks below the one checked
+    tip: Option<H256>, //parent of the block checked
+}
+
+/// The UTXO set of a shard, built by applying the blocks of its longest chain in order
+#[derive(Clone)]
+pub struct UtxoState {
+    shard_id: usize,
+    config: Configuration,
+    utxos: HashMap<Outpoint, Utxo>,
+    //hash of the Input transaction -> outputs locked by an outgoing transfer
+    locks: HashMap<H256, Lock>,
+    //hash of the Input transaction of the incoming transfers already accepted or rejected
+    settled: HashSet<H256>,
+    //applied blocks, ordered from genesis to the tip
+    applied_blocks: Vec<H256>,
+    undo_logs: HashMap<H256, BlockUndo>,
+    //tx_hash -> hash of the applied block containing it
+    tx_index: HashMap<H256, H256>,
+}
+
+impl UtxoState {
+    pub fn new(shard_id: usize, config: &Configuration) -> Self {
+        UtxoState {
+            shard_id,
+            config: config.clone(),
+            utxos: HashMap::new(),
+            locks: HashMap::new(),
+            settled: HashSet::new(),
+            applied_blocks: vec![],
+            undo_logs: HashMap::new(),
+            tx_index: HashMap::new(),
+        }
+    }
+
+    pub fn get_shard_id(&self) -> usize {
+        self.shard_id
+    }
+
+    /// The last applied block
+    pub fn tip(&self) -> Option<H256> {
+        self.applied_blocks.last().cloned()
+    }
+
+    pub fn get_utxo(&self, outpoint: &Outpoint) -> Option<Utxo> {
+        self.utxos.get(outpoint).cloned()
+    }
+
+    pub fn get_utxo_size(&self) -> usize {
+        self.utxos.len()
+    }
+
+    /// Whether the transaction is already in one of the applied blocks
+    pub fn contains_tx(&self, tx_hash: &H256) -> bool {
+        self.tx_index.contains_key(tx_hash)
+    }
+
+    /// The applied block containing the transaction
+    pub fn get_tx_block(&self, tx_hash: &H256) -> Option<H256> {
+        self.tx_index.get(tx_hash).cloned()
+    }
+
+    /// Outgoing transfers waiting for the destination shard
+    pub fn get_locks(&self) -> Vec<Lock> {
+        self.locks.values().cloned().collect()
+    }
+
+    /// Whether the incoming transfer of the Input transaction is already accepted or rejected
+    pub fn is_settled(&self, input_hash: &H256) -> bool {
+        self.settled.contains(input_hash)
+    }
+
+    /// Whether this shard, as the destination, can credit all the outputs of a transfer
+    pub fn can_accept(&self, tx: &Transaction) -> bool {
+        tx.outputs
+            .iter()
+            .all(|x| x.value > 0 && self.config.get_shard_id_by_addr(&x.receiver_addr) == self.shard_id)
+    }
+
+    //whether all the outputs are paid to addresses of the shard
+    fn pays_only_to(&self, tx: &Transaction, shard_id: usize) -> bool {
+        tx.outputs
+            .iter()
+            .all(|x| self.config.get_shard_id_by_addr(&x.receiver_addr) == shard_id)
+    }
+
+    /// The reward of a block packing `txs` on top of the current tip, 
+    /// paying the block subsidy and the fees of the transactions
+    pub fn create_block_reward(&self, txs: &[Transaction], receiver: &Address) -> Option<Transaction> {
+        let view = self.build_view(txs).ok()?;
+        let value = self.config.block_reward as u64 + view.fees;
+        Some(Transaction::create_coinbase_tx(
+            self.tip().unwrap_or_default(),
+            receiver,
+            value as usize,
+        ))
+    }
+
+    /// The fee left by a domestic transaction, its inputs minus its outputs.
+    /// Other transactions pay nothing, none when an input is not in the UTXO set.
+    pub fn get_fee(&self, tx: &Transaction) -> Option<u64> {
+        self.get_fee_with_ancestors(tx, &[])
+    }
+
+    /// The fee of a transaction which may also spend the outputs of pending `ancestors`
+    pub fn get_fee_with_ancestors(&self, tx: &Transaction, ancestors: &[Transaction]) -> Option<u64> {
+        if !matches!(tx.flag, TxFlag::Domestic) {
+            return Some(0);
+        }
+        let mut input_value: u64 = 0;
+        for input in tx.inputs.iter() {
+            let value = match self.get_utxo(&(input.tx_hash, input.index)) {
+                Some(utxo) => utxo.output.value,
+                None => ancestors
+                    .iter()
+                    .find(|x| x.hash() == input.tx_hash)?
+                    .outputs
+                    .get(input.index as usize)?
+                    .value,
+            };
+            input_value += value as u64;
+        }
+        let output_value: u64 = tx.outputs
+            .iter()
+            .map(|x| x.value as u64)
+            .sum();
+        input_value.checked_sub(output_value)
+    }
+
+    /// Check a single transaction against the current UTXO set
+    pub fn validate_tx(&self, tx: &Transaction) -> Result<bool, String> {
+        self.check_tx(tx, &self.new_view())
+    }
+
+    /// Check a transaction on top of its pending `ancestors`, parents before children, 
+    /// as if they were packed together before it
+    pub fn validate_tx_with_ancestors(&self, tx: &Transaction, ancestors: &[Transaction]) 
+        -> Result<bool, String> 
+    {
+        let view = self.build_view(ancestors)?;
+        self.check_tx(tx, &view)
+    }
+
+    /// Check the transactions of a block, in order, against the current UTXO set.
+    /// Later transactions may spend the outputs of earlier ones.
+    pub fn validate_txs(&self, txs: &[Transaction]) -> Result<bool, String> {
+        self.build_view(txs)?;
+        Ok(true)
+    }
+
+    /// Keep the transactions which can be packed together on top of the current tip, 
+    /// in order, skipping those already confirmed or invalid
+    pub fn select_txs(&self, txs: Vec<Transaction>) -> Vec<Transaction> {
+        let mut view = self.new_view();
+        let mut selected: Vec<Transaction> = vec![];
+        for tx in txs {
+            if self.check_tx(&tx, &view).is_ok() {
+                self.record_tx(&tx, &mut view);
+                selected.push(tx);
+            }
+        }
+        selected
+    }
+
+    /// Apply a block on top of the current tip
+    pub fn apply_block(&mut self, block_hash: &H256, txs: &[Transaction])
+        -> Result<bool, String>
+    {
+        let view = match self.build_view(txs) {
+            Ok(view) => view,
+            Err(e) => return Err(format!("Block {:?} is not valid: {}", block_hash, e)),
+        };
+        let mut undo = BlockUndo::default();
+        //outputs both created and spent in the block never reach the set
+        for outpoint in view.spent {
+            if let Some(utxo) = self.utxos.remove(&outpoint) {
+                undo.spent.push((outpoint, utxo));
+            }
+        }
+        for (outpoint, utxo) in view.created {
+            self.utxos.insert(outpoint, utxo);
+            undo.created.push(outpoint);
+        }
+        for input_hash in view.released {
+            if let Some(lock) = self.locks.remove(&input_hash) {
+                undo.released.push((input_hash, lock));
+            }
+        }
+        for (input_hash, lock) in view.locked {
+            self.locks.insert(input_hash, lock);
+            undo.locked.push(input_hash);
+        }
+        for input_hash in view.settled {
+            self.settled.insert(input_hash);
+            undo.settled.push(input_hash);
+        }
+        for tx_hash in view.txs {
+            self.tx_index.insert(tx_hash, *block_hash);
+            undo.txs.push(tx_hash);
+        }
+        self.applied_blocks.push(*block_hash);
+        self.undo_logs.insert(*block_hash, undo);
+        Ok(true)
+    }
+
+    /// Revert the tip block, returning its hash
+    pub fn undo_block(&mut self) -> Option<H256> {
+        let block_hash = self.applied_blocks.pop()?;
+        let undo = self.undo_logs.remove(&block_hash).unwrap();
+        for outpoint in undo.created.iter() {
+            self.utxos.remove(outpoint);
+        }
+        for (outpoint, utxo) in undo.spent {
+            self.utxos.insert(outpoint, utxo);
+        }
+        for input_hash in undo.locked.iter() {
+            self.locks.remove(input_hash);
+        }
+        for (input_hash, lock) in undo.released {
+            self.locks.insert(input_hash, lock);
+        }
+        for input_hash in undo.settled.iter() {
+            self.settled.remove(input_hash);
+        }
+        for tx_hash in undo.txs.iter() {
+            self.tx_index.remove(tx_hash);
+        }
+        Some(block_hash)
+    }
+
+    /// Move the state to the end of `chain` (ordered from genesis), reverting the blocks
+    /// which are no longer on it and applying the new ones
+    pub fn sync(&mut self, chain: &[H256], blockchain: &Blockchain) -> Result<bool, String> {
+        let common = self.applied_blocks
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while self.applied_blocks.len() > common {
+            self.undo_block();
+        }
+        for block_hash in chain[common..].iter() {
+            let txs = get_block_txs(block_hash, blockchain)?;
+            self.apply_block(block_hash, &txs)?;
+        }
+        Ok(true)
+    }
+
+    /// Check the transactions of a block extending the end of `chain` (ordered from genesis), 
+    /// which forks from the applied blocks. The state is left as it is: the blocks which are 
+    /// not on `chain` are reverted from their undo logs, and the new ones replayed, in a view.
+    pub fn validate_txs_on_chain(&self, txs: &[Transaction], chain: &[H256], blockchain: &Blockchain)
+        -> Result<bool, String>
+    {
+        let common = self.applied_blocks
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut view = self.new_view();
+        for block_hash in self.applied_blocks[common..].iter().rev() {
+            self.revert_in_view(block_hash, &mut view);
+        }
+        for block_hash in chain[common..].iter() {
+            let block_txs = get_block_txs(block_hash, blockchain)?;
+            view = self.extend_view(view, &block_txs)?;
+            view.height += 1;
+            view.tip = Some(*block_hash);
+        }
+        self.extend_view(view, txs)?;
+        Ok(true)
+    }
+
+    //a view on top of the current tip
+    fn new_view(&self) -> BlockView {
+        BlockView {
+            height: self.applied_blocks.len(),
+            tip: self.tip(),
+            ..BlockView::default()
+        }
+    }
+
+    //revert an applied block in the view, the blocks applied after it are reverted already
+    fn revert_in_view(&self, block_hash: &H256, view: &mut BlockView) {
+        let undo = &self.undo_logs[block_hash];
+        for outpoint in undo.created.iter() {
+            view.created.remove(outpoint);
+            view.spent.insert(*outpoint);
+        }
+        for (outpoint, utxo) in undo.spent.iter() {
+            view.spent.remove(outpoint);
+            view.created.insert(*outpoint, utxo.clone());
+        }
+        for input_hash in undo.locked.iter() {
+            view.locked.remove(input_hash);
+            view.released.insert(*input_hash);
+        }
+        for (input_hash, lock) in undo.released.iter() {
+            view.released.remove(input_hash);
+            view.locked.insert(*input_hash, lock.clone());
+        }
+        view.unsettled.extend(undo.settled.iter().cloned());
+        view.removed_txs.extend(undo.txs.iter().cloned());
+        view.height -= 1;
+        view.tip = view.height.checked_sub(1).map(|x| self.applied_blocks[x]);
+    }
+
+    fn build_view(&self, txs: &[Transaction]) -> Result<BlockView, String> {
+        self.extend_view(self.new_view(), txs)
+    }
+
+    //check the transactions of a block on top of the view
+    fn extend_view(&self, mut view: BlockView, txs: &[Transaction]) -> Result<BlockView, String> {
+        view.fees = 0;
+        //the reward of the block comes first, but it is checked once the fees are known
+        let (reward, txs) = match txs.split_first() {
+            Some((first, rest)) if self.is_block_reward(first, &view) => (Some(first), rest),
+            _ => (None, txs),
+        };
+        for tx in txs.iter() {
+            self.check_tx(tx, &view)?;
+            self.record_tx(tx, &mut view);
+        }
+        if let Some(reward) = reward {
+            self.check_block_reward(reward, &view)?;
+            self.record_tx(reward, &mut view);
+        }
+        Ok(view)
+    }
+
+    //the reward of a shard block refers to its parent, the tip of the view
+    fn is_block_reward(&self, tx: &Transaction, view: &BlockView) -> bool {
+        matches!(tx.flag, TxFlag::Coinbase) 
+            && tx.inputs.len() == 1
+            && tx.inputs[0].tx_hash == view.tip.unwrap_or_default()
+    }
+
+    //a reward pays one output in this shard
+    fn check_reward_output(&self, tx: &Transaction) -> Result<u64, String> {
+        let tx_hash = tx.hash();
+        if tx.inputs.len() != 1 || tx.outputs.len() != 1 {
+            return Err(format!("Transaction {:?} is not a well-formed reward", tx_hash));
+        }
+        if !self.is_destination(tx) {
+            return Err(format!("Transaction {:?} pays a reward to another shard", tx_hash));
+        }
+        Ok(tx.outputs[0].value as u64)
+    }
+
+    //the reward of a shard block is at most the subsidy plus the fees of the block
+    fn check_block_reward(&self, tx: &Transaction, view: &BlockView) -> Result<bool, String> {
+        let tx_hash = tx.hash();
+        if self.has_tx(&tx_hash, view) {
+            return Err(format!("Transaction {:?} already exists", tx_hash));
+        }
+        let value = self.check_reward_output(tx)?;
+        let limit = self.config.block_reward as u64 + view.fees;
+        if value > limit {
+            return Err(format!(
+                "Transaction {:?} claims a reward of {} over {}",
+                tx_hash,
+                value,
+                limit
+            ));
+        }
+        Ok(true)
+    }
+
+    fn find_utxo<'a>(&'a self, outpoint: &Outpoint, view: &'a BlockView)
+        -> Option<&'a Utxo>
+    {
+        if view.spent.contains(outpoint) {
+            return None;
+        }
+        match view.created.get(outpoint) {
+            Some(utxo) => Some(utxo),
+            None => self.utxos.get(outpoint),
+        }
+    }
+
+    fn find_lock<'a>(&'a self, input_hash: &H256, view: &'a BlockView) -> Option<&'a Lock> {
+        if view.released.contains(input_hash) {
+            return None;
+        }
+        match view.locked.get(input_hash) {
+            Some(lock) => Some(lock),
+            None => self.locks.get(input_hash),
+        }
+    }
+
+    fn has_tx(&self, tx_hash: &H256, view: &BlockView) -> bool {
+        view.txs.contains(tx_hash)
+            || (self.tx_index.contains_key(tx_hash) && !view.removed_txs.contains(tx_hash))
+    }
+
+    fn owns_inputs(&self, tx: &Transaction) -> bool {
+        tx.inputs
+            .iter()
+            .all(|x| self.config.get_shard_id_by_addr(&x.sender_addr) == self.shard_id)
+    }
+
+    fn is_source(&self, tx: &Transaction) -> bool {
+        self.config.get_shard_id_by_addr(&tx.get_sender_addr()) == self.shard_id
+    }
+
+    fn is_destination(&self, tx: &Transaction) -> bool {
+        self.config.get_shard_id_by_addr(&tx.get_receiver_addr()) == self.shard_id
+    }
+
+    fn check_tx(&self, tx: &Transaction, view: &BlockView) -> Result<bool, String> {
+        let tx_hash = tx.hash();
+        if self.has_tx(&tx_hash, view) {
+            return Err(format!("Transaction {:?} already exists", tx_hash));
+        }
+        if tx.outputs.is_empty() {
+            return Err(format!("Transaction {:?} has no output", tx_hash));
+        }
+        match tx.flag {
+            TxFlag::Initial => {
+                //value is only minted by the genesis allocation, 
+                //the random transactions of the synthetic workload are not backed by any
+                if view.height > 0 && !self.config.synthetic_txs {
+                    return Err(format!("Transaction {:?} mints value outside the genesis", tx_hash));
+                }
+                Ok(true)
+            }
+            TxFlag::Coinbase => {
+                //the reward of an order block, the multichain checks the rewarded block
+                if self.check_reward_output(tx)? != self.config.order_reward as u64 {
+                    return Err(format!("Transaction {:?} has a wrong order block reward", tx_hash));
+                }
+                Ok(true)
+            }
+            TxFlag::Domestic => {
+                //a transfer touching another shard must be flagged as cross-shard
+                if !self.owns_inputs(tx) {
+                    return Err(format!("Transaction {:?} spends outputs of another shard", tx_hash));
+                }
+                if !self.pays_only_to(tx, self.shard_id) {
+                    return Err(format!("Transaction {:?} pays to another shard", tx_hash));
+                }
+                self.check_spending(tx, view)
+            }
+            TxFlag::Input => {
+                //all the inputs must belong to this shard, and the outputs to another one
+                if !self.owns_inputs(tx) {
+                    return Err(format!("Transaction {:?} spends outputs of another shard", tx_hash));
+                }
+                if self.is_destination(tx) {
+                    return Err(format!("Transaction {:?} does not leave the shard", tx_hash));
+                }
+                //the destination credits every output, a change output back to the sender 
+                //could never be accepted
+                let destination = self.config.get_shard_id_by_addr(&tx.get_receiver_addr());
+                if !self.pays_only_to(tx, destination) {
+                    return Err(format!("Transaction {:?} pays outside its destination shard", tx_hash));
+                }
+                self.check_spending(tx, view)
+            }
+            TxFlag::Output => {
+                if !self.is_source(tx) {
+                    return Err(format!("Transaction {:?} is not from this shard", tx_hash));
+                }
+                self.check_lock(tx, view)
+            }
+            TxFlag::Accept => {
+                if !self.is_destination(tx) {
+                    return Err(format!("Transaction {:?} is not to this shard", tx_hash));
+                }
+                if !self.can_accept(tx) {
+                    return Err(format!("Transaction {:?} cannot be accepted", tx_hash));
+                }
+                self.check_settlement(tx, view)
+            }
+            TxFlag::Reject => {
+                //the source refunds what the destination rejected
+                if self.is_source(tx) {
+                    return self.check_lock(tx, view);
+                }
+                if !self.is_destination(tx) {
+                    return Err(format!("Transaction {:?} is not related to this shard", tx_hash));
+                }
+                if self.can_accept(tx) {
+                    return Err(format!("Transaction {:?} cannot be rejected", tx_hash));
+                }
+                self.check_settlement(tx, view)
+            }
+        }
+    }
+
+    //check that the inputs are unspent and signed by their owners, and balance the outputs
+    fn check_spending(&self, tx: &Transaction, view: &BlockView) -> Result<bool, String> {
+        let tx_hash = tx.hash();
+        if tx.inputs.is_empty() {
+            return Err(format!("Transaction {:?} has no input", tx_hash));
+        }
+        let mut used: HashSet<Outpoint> = HashSet::new();
+        let mut input_value: u64 = 0;
+        for (index, input) in tx.inputs.iter().enumerate() {
+            let outpoint = (input.tx_hash, input.index);
+            if !used.insert(outpoint) {
+                return Err(format!("Transaction {:?} double spends an input", tx_hash));
+            }
+            let utxo = match self.find_utxo(&outpoint, view) {
+                Some(utxo) => utxo,
+                None => {
+                    return Err(format!(
+                        "Transaction {:?} spends an unknown or spent output {:?}:{}",
+                        tx_hash,
+                        input.tx_hash,
+                        input.index
+                    ));
+                }
+            };
+            if utxo.output.value != input.value {
+                return Err(format!("Transaction {:?} has a wrong input value", tx_hash));
+            }
+            if utxo.coinbase && view.height < utxo.height + self.config.coinbase_maturity {
+                return Err(format!("Transaction {:?} spends an immature reward", tx_hash));
+            }
+            if utxo.output.receiver_addr != input.sender_addr {
+                return Err(format!("Transaction {:?} spends an output of another address", tx_hash));
+            }
+            if Address::from_public_key(&input.public_key_ref) != Some(utxo.output.receiver_addr) {
+                return Err(format!("Transaction {:?} reveals a key not matching the spent address", tx_hash));
+            }
+            if !Transaction::verify(tx, index, &input.public_key_ref, &input.sig_ref) {
+                return Err(format!("Transaction {:?} has an invalid signature", tx_hash));
+            }
+            input_value += input.value as u64;
+        }
+        let output_value: u64 = tx.outputs
+            .iter()
+            .map(|x| x.value as u64)
+            .sum();
+        //a domestic transaction leaves the difference as fee, a cross-shard one 
+        //is balanced since a refund returns all its inputs
+        if input_value < output_value
+            || (matches!(tx.flag, TxFlag::Input) && input_value != output_value) {
+            return Err(format!(
+                "Transaction {:?} is not balanced: input {} output {}",
+                tx_hash,
+                input_value,
+                output_value
+            ));
+        }
+        Ok(true)
+    }
+
+    //the source shard finishes an outgoing transfer, its inputs must still be locked
+    fn check_lock(&self, tx: &Transaction, view: &BlockView) -> Result<bool, String> {
+        let input_hash = tx.get_related_hash(TxFlag::Input);
+        match self.find_lock(&input_hash, view) {
+            Some(_) => Ok(true),
+            None => Err(format!("Transaction {:?} has no locked input", tx.hash())),
+        }
+    }
+
+    //the destination shard decides on an incoming transfer only once
+    fn check_settlement(&self, tx: &Transaction, view: &BlockView) -> Result<bool, String> {
+        let input_hash = tx.get_related_hash(TxFlag::Input);
+        if view.settled.contains(&input_hash)
+            || (self.settled.contains(&input_hash) && !view.unsettled.contains(&input_hash)) {
+            return Err(format!("Transaction {:?} is already settled", tx.hash()));
+        }
+        Ok(true)
+    }
+
+    fn record_tx(&self, tx: &Transaction, view: &mut BlockView) {
+        let tx_hash = tx.hash();
+        let mut spent: Vec<Utxo> = vec![];
+        if let TxFlag::Domestic | TxFlag::Input = tx.flag {
+            for input in tx.inputs.iter() {
+                let outpoint = (input.tx_hash, input.index);
+                if let Some(utxo) = self.find_utxo(&outpoint, view) {
+                    spent.push(utxo.clone());
+                }
+                view.created.remove(&outpoint);
+                view.spent.insert(outpoint);
+            }
+            let input_value: u64 = tx.inputs.iter().map(|x| x.value as u64).sum();
+            let output_value: u64 = tx.outputs.iter().map(|x| x.value as u64).sum();
+            view.fees += input_value.saturating_sub(output_value);
+        }
+        let input_hash = tx.get_related_hash(TxFlag::Input);
+        let created: Vec<UtxoOutput> = match tx.flag {
+            TxFlag::Initial | TxFlag::Domestic | TxFlag::Coinbase => tx.outputs.clone(),
+            TxFlag::Input => {
+                view.locked.insert(tx_hash, Lock {
+                    input_tx: tx.clone(),
+                    spent,
+                });
+                vec![]
+            }
+            TxFlag::Output => {
+                view.locked.remove(&input_hash);
+                view.released.insert(input_hash);
+                vec![]
+            }
+            TxFlag::Accept => {
+                view.settled.insert(input_hash);
+                tx.outputs.clone()
+            }
+            TxFlag::Reject => {
+                if self.is_source(tx) {
+                    //refund the locked outputs to their owners
+                    let refunds: Vec<UtxoOutput> = self.find_lock(&input_hash, view)
+                        .map(|lock| lock.spent.iter().map(|x| x.output.clone()).collect())
+                        .unwrap_or_default();
+                    view.locked.remove(&input_hash);
+                    view.released.insert(input_hash);
+                    refunds
+                } else {
+                    view.settled.insert(input_hash);
+                    vec![]
+                }
+            }
+        };
+        let height = view.height;
+        let coinbase = matches!(tx.flag, TxFlag::Coinbase);
+        for (index, output) in created.into_iter().enumerate() {
+            view.created.insert((tx_hash, index as u32), Utxo { output, height, coinbase });
+        }
+        view.txs.insert(tx_hash);
+    }
+}
+
+//the transactions of a block of the shard chain
+fn get_block_txs(block_hash: &H256, blockchain: &Blockchain) -> Result<Vec<Transaction>, String> {
+    match blockchain.get_block(block_hash) {
+        Some(VersaBlock::ShardBlock(shard_block)) => Ok(shard_block.get_txs()),
+        Some(VersaBlock::OrderBlock(_)) => Err(format!("Order block {:?} in a shard chain", block_hash)),
+        None => Err(format!("Block {:?} not found", block_hash)),
+    }
+}
@@ -2,7 +2,11 @@ use serde::{Serialize, Deserialize};
 use ring::signature::{self, Ed25519KeyPair, Signature, KeyPair};
 use crate::types::hash::{H256, Hashable};
 use rand::{self, Rng};
-use crate::types::{random::Random, key_pair};
+use crate::types::{
+    random::Random,
+    address::Address,
+    encoding::{self, Encodable, Decodable, Reader},
+};
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
 pub enum TxFlag{
     Initial,
@@ -11,9 +15,47 @@ pub enum TxFlag{
     Output,
     Accept,
     Reject,
+    Coinbase,
 }
 
 
+impl TxFlag {
+    /// Steps of a cross-shard transfer and block rewards, which are produced by miners, not by users
+    pub fn is_generated(&self) -> bool {
+        matches!(self, TxFlag::Output | TxFlag::Accept | TxFlag::Reject | TxFlag::Coinbase)
+    }
+}
+
+impl Encodable for TxFlag {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            TxFlag::Initial => 0,
+            TxFlag::Domestic => 1,
+            TxFlag::Input => 2,
+            TxFlag::Output => 3,
+            TxFlag::Accept => 4,
+            TxFlag::Reject => 5,
+            TxFlag::Coinbase => 6,
+        };
+        tag.encode(out);
+    }
+}
+
+impl Decodable for TxFlag {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match u8::decode(reader)? {
+            0 => Ok(TxFlag::Initial),
+            1 => Ok(TxFlag::Domestic),
+            2 => Ok(TxFlag::Input),
+            3 => Ok(TxFlag::Output),
+            4 => Ok(TxFlag::Accept),
+            5 => Ok(TxFlag::Reject),
+            6 => Ok(TxFlag::Coinbase),
+            x => Err(format!("Unknown transaction flag {}", x)),
+        }
+    }
+}
+
 impl Default for TxFlag {
     fn default() -> Self {
         TxFlag::Domestic
@@ -29,6 +71,7 @@ impl ToString for TxFlag {
             TxFlag::Output => String::from("output"),
             TxFlag::Accept => String::from("accept"),
             TxFlag::Reject => String::from("reject"),
+            TxFlag::Coinbase => String::from("coinbase"),
         }
     }
 }
@@ -41,30 +84,28 @@ pub struct Transaction {
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
 pub struct UtxoInput {
-    pub sender_addr: H256,
+    pub sender_addr: Address,
     pub tx_hash: H256,
     pub value: u32,
     pub index: u32,
+    pub public_key_ref: Vec<u8>, //revealed key, it must hash to the address of the spent output
     pub sig_ref: Vec<u8>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, Hash, PartialEq)]
 pub struct UtxoOutput {
-    pub receiver_addr: H256,
+    pub receiver_addr: Address,
     pub value: u32,
-    pub public_key_ref: Vec<u8>,
 }
 
 impl Random for Transaction {
     fn random() -> Self {
         let mut rng = rand::thread_rng();
-        let rand_addr: [u8; 32] = rng.gen();
-        let rand_addr_hash = (&rand_addr).into();
+        let rand_addr: [u8; 20] = rng.gen();
         let input = UtxoInput::default();
         let output = UtxoOutput {
-            receiver_addr: rand_addr_hash,
+            receiver_addr: rand_addr.into(),
             value: rng.gen_range(1..1000) as u32,
-            public_key_ref: key_pair::random().public_key().as_ref().to_vec(),
         };
         let inputs: Vec<UtxoInput> = vec![input];
         let outputs: Vec<UtxoOutput> = vec![output];
@@ -79,73 +120,93 @@ impl Random for Transaction {
 
 impl Hashable for UtxoInput {
     fn hash(&self) -> H256 {
-        let sig_ref_str: String = serde_json::to_string(&self.sig_ref).unwrap();
-        let input_str = format!("{}{}{}", 
-            self.value,
-            self.index,
-            sig_ref_str,
-        );
-        let tmp_hash: H256 = ring::digest::digest(
-            &ring::digest::SHA256, input_str.as_bytes()
-        ).into();
-        let hash_vec: Vec<H256> = vec![
-            self.sender_addr.clone(), 
-            tmp_hash, 
-            self.tx_hash.clone()
-        ];
-        H256::multi_hash(&hash_vec)
+        encoding::digest(self)
+    }
+}
+
+impl Encodable for UtxoInput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.sender_addr.encode(out);
+        self.tx_hash.encode(out);
+        self.value.encode(out);
+        self.index.encode(out);
+        self.public_key_ref.encode(out);
+        self.sig_ref.encode(out);
     }
 }
+
+impl Decodable for UtxoInput {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        Ok(UtxoInput {
+            sender_addr: Address::decode(reader)?,
+            tx_hash: H256::decode(reader)?,
+            value: u32::decode(reader)?,
+            index: u32::decode(reader)?,
+            public_key_ref: Vec::<u8>::decode(reader)?,
+            sig_ref: Vec::<u8>::decode(reader)?,
+        })
+    }
+}
+
 impl UtxoInput {
     pub fn get_mem_size(&self) -> usize {
-        H256::get_mem_size() * 2 
+        H256::get_mem_size()
+            + std::mem::size_of::<Address>()
             + std::mem::size_of::<u32>() * 2 
-            + std::mem::size_of::<u8>() * self.sig_ref.len()
+            + std::mem::size_of::<u8>() * (self.public_key_ref.len() + self.sig_ref.len())
     }
 }
 
 impl Hashable for UtxoOutput {
     fn hash(&self) -> H256 {
-        let public_key_ref_str: String = serde_json::to_string(&self.public_key_ref).unwrap();
-        let output_str = format!("{}{}",
-            self.value,
-            public_key_ref_str
-        );
-        let tmp_hash: H256 = ring::digest::digest(
-            &ring::digest::SHA256, output_str.as_bytes()
-        ).into();
-        let hash_vec: Vec<H256> = vec![self.receiver_addr.clone(), tmp_hash];
-        H256::multi_hash(&hash_vec)
+        encoding::digest(self)
+    }
+}
+
+impl Encodable for UtxoOutput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.receiver_addr.encode(out);
+        self.value.encode(out);
+    }
+}
+
+impl Decodable for UtxoOutput {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        Ok(UtxoOutput {
+            receiver_addr: Address::decode(reader)?,
+            value: u32::decode(reader)?,
+        })
     }
 }
 
 impl UtxoOutput {
     pub fn get_mem_size(&self) -> usize {
-        H256::get_mem_size()
-            + std::mem::size_of::<u32>() 
-            + std::mem::size_of::<u8>() * self.public_key_ref.len()
+        std::mem::size_of::<Address>()
+            + std::mem::size_of::<u32>()
     }
 }
 
 impl Hashable for Transaction {
     fn hash(&self) -> H256 {
-        let mut input_hash_vec: Vec<H256> = self.inputs
-            .clone()
-            .into_iter()
-            .map(|x| x.hash())
-            .collect();
-        let out_hash_vec: Vec<H256> = self.outputs
-            .clone()
-            .into_iter()
-            .map(|x| x.hash())
-            .collect();
-        input_hash_vec.extend(out_hash_vec);
-        let flag_hash: H256 = ring::digest::digest(
-            &ring::digest::SHA256,
-            self.flag.to_string().as_bytes()
-        ).into();
-        input_hash_vec.push(flag_hash);
-        H256::multi_hash(&input_hash_vec)
+        encoding::digest(self)
+    }
+}
+
+impl Encodable for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.inputs.encode(out);
+        self.outputs.encode(out);
+        self.flag.encode(out);
+    }
+}
+
+impl Decodable for Transaction {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        Ok(Transaction {
+            inputs: Vec::<UtxoInput>::decode(reader)?,
+            outputs: Vec::<UtxoOutput>::decode(reader)?,
+            flag: TxFlag::decode(reader)?,
+        })
     }
 }
 
@@ -201,20 +262,31 @@ impl Transaction {
         }
         input_mem_size + output_mem_size + std::mem::size_of::<TxFlag>()
     }
-    /// Create digital signature of a transaction
-    pub fn sign(t: &Transaction, key: &Ed25519KeyPair) -> Signature {
-        let serialized_tx: Vec<u8> = bincode::serialize(t).unwrap();
-        key.sign(serialized_tx.as_slice())
+    /// The digest signed by input `index`: the transaction without any signature, then the
+    /// index, so a signature commits to every outpoint, value and output and cannot be moved
+    /// to another input or another transaction
+    pub fn sighash(&self, index: usize) -> H256 {
+        let mut unsigned = self.clone();
+        for input in unsigned.inputs.iter_mut() {
+            input.sig_ref.clear();
+        }
+        let mut bytes = encoding::serialize(&unsigned);
+        (index as u32).encode(&mut bytes);
+        ring::digest::digest(&ring::digest::SHA256, &bytes).into()
+    }
+
+    /// Create digital signature of input `index` of a transaction
+    pub fn sign(t: &Transaction, index: usize, key: &Ed25519KeyPair) -> Signature {
+        key.sign(t.sighash(index).as_ref())
     }
 
-    /// Verify digital signature of a transaction, using public key instead of secret key
-    pub fn verify(t: &Transaction, public_key_ref: &[u8], sig_ref: &[u8]) -> bool {
+    /// Verify digital signature of input `index` of a transaction, using public key instead of secret key
+    pub fn verify(t: &Transaction, index: usize, public_key_ref: &[u8], sig_ref: &[u8]) -> bool {
         let peer_public_key = signature::UnparsedPublicKey::new(
             &signature::ED25519, 
             public_key_ref
         );
-        let serialized_tx: Vec<u8> = bincode::serialize(t).unwrap();
-        let res = peer_public_key.verify(serialized_tx.as_slice(), sig_ref);
+        let res = peer_public_key.verify(t.sighash(index).as_ref(), sig_ref);
         match res {
             Ok(()) => {
                 true
@@ -225,6 +297,8 @@ impl Transaction {
         }
     }
 
+    /// Verify every input against the output it spends, `input_txs` are the funding transactions:
+    /// the revealed key must hash to the address of the output and sign the input
     pub fn verify_owner(
         tx: &Transaction,  
         input_txs: Vec<&Transaction>, 
@@ -234,30 +308,55 @@ impl Transaction {
         }
         for i in 0..tx.inputs.len() {
             let input = &tx.inputs[i];
-            let input_tx = input_txs[i];
-            if !Self::verify(
-                input_tx,
-                &input_tx.outputs[input.index as usize].public_key_ref,
-                &input.sig_ref,
-            ) {
+            let output = match input_txs[i].outputs.get(input.index as usize) {
+                Some(output) => output,
+                None => return false,
+            };
+            if Address::from_public_key(&input.public_key_ref) != Some(output.receiver_addr) {
+                return false;
+            }
+            if !Self::verify(tx, i, &input.public_key_ref, &input.sig_ref) {
                 return false;
             }
         }
         true
     }
 
+    /// The address paying for the transaction, an initial or coinbase transaction has no sender 
+    /// so its receiver is used instead
+    pub fn get_sender_addr(&self) -> Address {
+        match self.flag {
+            TxFlag::Initial | TxFlag::Coinbase => match self.outputs.first() {
+                Some(output) => output.receiver_addr,
+                None => Address::default(),
+            },
+            _ => match self.inputs.first() {
+                Some(input) => input.sender_addr,
+                None => Address::default(),
+            },
+        }
+    }
+
+    /// The address receiving the first output, it decides the destination of a cross-shard transfer
+    pub fn get_receiver_addr(&self) -> Address {
+        match self.outputs.first() {
+            Some(output) => output.receiver_addr,
+            None => Address::default(),
+        }
+    }
+
+    /// Hash of the same transfer at another step of the cross-shard protocol
     pub fn get_related_hash(&self, flag: TxFlag) -> H256 {
         let mut tx = self.clone();
         tx.flag = flag;
         tx.hash()
     }
 
-    pub fn create_initial_tx(user: (&H256, &Ed25519KeyPair), value: usize) -> Transaction {
+    pub fn create_initial_tx(receiver: &Address, value: usize) -> Transaction {
         let input = UtxoInput::default();
         let output = UtxoOutput {
-            receiver_addr: user.0.clone(),
+            receiver_addr: *receiver,
             value: value as u32,
-            public_key_ref: user.1.public_key().as_ref().to_vec(),
         };
         Transaction {
             inputs: vec![input],
@@ -266,10 +365,28 @@ impl Transaction {
         }
     }
 
+    /// The reward of a block, its only input refers to the rewarded block: the shard parent 
+    /// for the reward of a shard block, the block itself for the reward of an order block
+    pub fn create_coinbase_tx(rewarded: H256, receiver: &Address, value: usize) -> Transaction {
+        let input = UtxoInput {
+            tx_hash: rewarded,
+            ..Default::default()
+        };
+        let output = UtxoOutput {
+            receiver_addr: *receiver,
+            value: value as u32,
+        };
+        Transaction {
+            inputs: vec![input],
+            outputs: vec![output],
+            flag: TxFlag::Coinbase,
+        }
+    }
+
     pub fn consume(
         utxos: Vec<(&Transaction, usize)>, //tx, index 
-        senders: Vec<(&H256, &Ed25519KeyPair)>, //user_addr, user_key, sent_coin
-        receivers: Vec<(&H256, &Ed25519KeyPair, usize)>, //user_addr, user_key, received_coin
+        senders: Vec<&Ed25519KeyPair>, //owner key of each spent output
+        receivers: Vec<(&Address, usize)>, //user_addr, received_coin
         flag: TxFlag,
     ) -> Option<Transaction> {
 
@@ -283,37 +400,42 @@ impl Transaction {
             let input = match &tx.flag {
                 &TxFlag::Initial => {
                     UtxoInput {
-                        sender_addr: sender.0.clone(),
+                        sender_addr: Address::from_key_pair(sender),
                         tx_hash: tx.hash(),
                         value: tx.outputs[index].value,
                         index: index as u32,
-                        sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        public_key_ref: sender.public_key().as_ref().to_vec(),
+                        sig_ref: vec![],
                     }
                 }
-                &TxFlag::Domestic => {
+                &TxFlag::Domestic | &TxFlag::Coinbase => {
                     UtxoInput {
-                        sender_addr: sender.0.clone(),
+                        sender_addr: Address::from_key_pair(sender),
                         tx_hash: tx.hash(),
                         value: tx.outputs[index].value,
                         index: index as u32,
-                        sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        public_key_ref: sender.public_key().as_ref().to_vec(),
+                        sig_ref: vec![],
                     }
                 }
-                &TxFlag::Output => {
+                &TxFlag::Accept => {
                     UtxoInput {
-                        sender_addr: sender.0.clone(),
+                        sender_addr: Address::from_key_pair(sender),
                         tx_hash: tx.hash(),
                         value: tx.outputs[index].value,
                         index: index as u32,
-                        sig_ref: Transaction::sign(tx, sender.1).as_ref().to_vec(),
+                        public_key_ref: sender.public_key().as_ref().to_vec(),
+                        sig_ref: vec![],
                     }
                 }
+                //a refunded input of a rejected transfer
                 &TxFlag::Reject => {
                     UtxoInput {
-                        sender_addr: sender.0.clone(),
+                        sender_addr: Address::from_key_pair(sender),
                         tx_hash: tx.hash(),
                         value: tx.inputs[index].value,
                         index: index as u32,
+                        public_key_ref: sender.public_key().as_ref().to_vec(),
                         sig_ref: vec![],
                     }
                 }
@@ -329,24 +451,29 @@ impl Transaction {
         let mut received_coins = 0;
         for x in receivers {
             let output  = UtxoOutput {
-                receiver_addr: x.0.clone(),
-                value: x.2 as u32,
-                public_key_ref: x.1.public_key().as_ref().to_vec(),
+                receiver_addr: *x.0,
+                value: x.1 as u32,
             };
             outputs.push(output);
-            received_coins += x.2;
+            received_coins += x.1;
         }
 
-        if sent_coins != received_coins {
+        //what is not received is left as fee to the miner
+        if sent_coins < received_coins {
             return None;
         }
         
 
-        Some(Transaction {
+        let mut tx = Transaction {
             inputs,
             outputs,
             flag,
-        })
+        };
+        //every input signs the complete transaction
+        for (i, sender) in senders.iter().enumerate().take(tx.inputs.len()) {
+            tx.inputs[i].sig_ref = Transaction::sign(&tx, i, sender).as_ref().to_vec();
+        }
+        Some(tx)
                 
     }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    types::hash::{H256, Hashable},
+    sharding_bitcoin::{
+        block::{
+            Content,
+            Info,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        configuration::Configuration,
+        multichain::Multichain,
+        transaction::TxFlag,
+    },
+};
+use std::time::{Duration, SystemTime};
+
+/// Consensus checks applied to a block before it is inserted into the multichain.
+/// The checks against the chains take the multichain locked by the caller, 
+/// so that it cannot change before the block is inserted.
+#[derive(Clone)]
+pub struct Validator {
+    config: Configuration,
+}
+
+impl Validator {
+    pub fn new(config: &Configuration) -> Self {
+        Validator {
+            config: config.clone(),
+        }
+    }
+
+    /// Validate an incoming block on its own, the error describes why the block is rejected.
+    /// The timestamp is checked against the local clock apart, as a block ahead of it may 
+    /// become valid later.
+    pub fn validate_block(&self, block: &VersaBlock) -> Result<bool, String> {
+        //the stored hash must match the header and the nonce
+        if !block.verify_hash() {
+            return Err(String::from("Incorrect hash"));
+        }
+        if block.get_shard_id() >= self.config.shard_num {
+            return Err(format!("Unknown shard {}", block.get_shard_id()));
+        }
+        self.validate_pow(block)?;
+        if let VersaBlock::ShardBlock(shard_block) = block {
+            if !shard_block.verify_merkle_root() {
+                return Err(String::from("Transactions do not match the merkle root"));
+            }
+        }
+        self.validate_block_reward(block)?;
+        Ok(true)
+    }
+
+    /// Check that the reward of a shard block, the first transaction when it is a coinbase
+    /// referring to the shard parent, pays the address committed in the header
+    pub fn validate_block_reward(&self, block: &VersaBlock) -> Result<bool, String> {
+        let shard_block = match block {
+            VersaBlock::ShardBlock(shard_block) => shard_block,
+            VersaBlock::OrderBlock(_) => return Ok(true),
+        };
+        let txs = shard_block.get_txs();
+        let reward = match txs.first() {
+            Some(tx) if matches!(tx.flag, TxFlag::Coinbase) 
+                && tx.inputs.first().map(|x| x.tx_hash) == Some(shard_block.get_shard_parent()) => tx,
+            _ => return Ok(true),
+        };
+        if reward.get_receiver_addr() != shard_block.get_coinbase_addr() {
+            return Err(String::from("Block reward does not pay the coinbase address"));
+        }
+        Ok(true)
+    }
+
+    /// Check that the block hash meets the target committed in its header
+    pub fn validate_pow(&self, block: &VersaBlock) -> Result<bool, String> {
+        let blk_hash: H256 = block.hash();
+        let target = block.get_target();
+        if blk_hash > target {
+            return match block {
+                VersaBlock::ShardBlock(_) => Err(format!(
+                    "Shard block hash {} is above the block target {}",
+                    blk_hash, target
+                )),
+                VersaBlock::OrderBlock(_) => Err(format!(
+                    "Order block hash {} is above the order target {}",
+                    blk_hash, target
+                )),
+            };
+        }
+        Ok(true)
+    }
+
+    /// Check that the block timestamp is not too far ahead of the local clock
+    pub fn validate_time_drift(&self, block: &VersaBlock) -> Result<bool, String> {
+        if self.config.max_time_drift == 0 {
+            return Ok(true);
+        }
+        let latest = SystemTime::now() + Duration::from_millis(self.config.max_time_drift);
+        if block.get_timestamp() > latest {
+            return Err(String::from("Timestamp is too far in the future"));
+        }
+        Ok(true)
+    }
+
+    /// Check that the block timestamp is later than the median time past of its parent
+    pub fn validate_median_time_past(&self, multichain: &Multichain, block: &VersaBlock) 
+        -> Result<bool, String> 
+    {
+        if self.config.median_time_span == 0 {
+            return Ok(true);
+        }
+        let median_time_past = match multichain.get_block_median_time_past(block) {
+            Some(time) => time,
+            None => return Err(String::from("Parent doesn't exisit")),
+        };
+        if block.get_timestamp() <= median_time_past {
+            return Err(String::from("Timestamp is not later than the median time past"));
+        }
+        Ok(true)
+    }
+
+    /// Check that the target committed in the header is the one the chain expects after
+    /// the parent, only the target of the chain the block belongs to is checked
+    pub fn validate_target(&self, multichain: &Multichain, block: &VersaBlock) -> Result<bool, String> {
+        let expected = match multichain.get_block_target(block) {
+            Some(target) => target,
+            None => return Err(String::from("Parent doesn't exisit")),
+        };
+        if block.get_target() != expected {
+            return Err(format!(
+                "Committed target {} does not match the expected target {}",
+                block.get_target(), expected
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Validate a block against the chain it extends, the parent must already exist
+    pub fn validate_with_parent(&self, multichain: &Multichain, block: &VersaBlock) 
+        -> Result<bool, String> 
+    {
+        self.validate_target(multichain, block)?;
+        self.validate_median_time_past(multichain, block)?;
+        match block {
+            VersaBlock::ShardBlock(shard_block) => {
+                multichain
+                    .validate_shard_txs_with_parent(
+                        shard_block.get_shard_id(),
+                        &shard_block.get_shard_parent(),
+                        &shard_block.get_order_parent(),
+                        &shard_block.get_txs(),
+                    )
+            }
+            VersaBlock::OrderBlock(order_block) => {
+                multichain
+                    .validate_confirmed_shard_blocks(
+                        &order_block.get_order_parent(),
+                        &order_block.get_confirmed_shard_blocks(),
+                    )
+            }
+        }
+    }
+
+    /// The shard blocks an order block confirms which do not have k blocks on top of them yet, 
+    /// the block may become valid once they do
+    pub fn get_shallow_refs(&self, multichain: &Multichain, block: &VersaBlock) -> Vec<H256> {
+        match block {
+            VersaBlock::OrderBlock(order_block) => {
+                multichain
+                    .get_shallow_shard_blocks(&order_block.get_confirmed_shard_blocks())
+            }
+            VersaBlock::ShardBlock(_) => vec![],
+        }
+    }
+
+    /// The blocks referred to besides the parent which have not arrived yet: the shard blocks
+    /// an order block confirms, or the order parent of a shard block. The block cannot be 
+    /// validated before they do.
+    pub fn get_missing_refs(&self, multichain: &Multichain, block: &VersaBlock) -> Vec<VersaHash> {
+        match block {
+            VersaBlock::OrderBlock(order_block) => {
+                multichain
+                    .get_missing_shard_blocks(&order_block.get_confirmed_shard_blocks())
+                    .into_iter()
+                    .map(VersaHash::ShardHash)
+                    .collect()
+            }
+            VersaBlock::ShardBlock(shard_block) => {
+                let order_parent = shard_block.get_order_parent();
+                match multichain.get_order_block(&order_parent) {
+                    Some(_) => vec![],
+                    None => vec![VersaHash::OrderHash(order_parent)],
+                }
+            }
+        }
+    }
+}
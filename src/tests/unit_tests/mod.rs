@@ -1,5 +1,4 @@
 #[cfg(test)]
 pub mod manifoldchain;
 pub mod optchain;
-
-
+pub mod sharding_bitcoin;
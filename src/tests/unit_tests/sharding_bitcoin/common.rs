@@ -0,0 +1,47 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::VersaBlock,
+        },
+        blockchain::Blockchain,
+        configuration::Configuration,
+        multichain::Multichain,
+        transaction::Transaction,
+    },
+    types::{
+        key_pair,
+        address::Address,
+    },
+};
+use ring::signature::Ed25519KeyPair;
+
+//the genesis of each shard holds the allocation to its addresses
+pub fn create_multichain(config: &Configuration, allocation: &[Transaction]) -> Multichain {
+    let order_chain = Blockchain::new(VersaBlock::OrderBlock(OrderBlock::default()), config);
+    let shard_chains: Vec<Blockchain> = (0..config.shard_num)
+        .map(|i| {
+            let mut header = BlockHeader::default();
+            header.set_shard_id(i);
+            let txs: Vec<Transaction> = allocation
+                .iter()
+                .filter(|x| config.get_shard_id_by_addr(&x.get_receiver_addr()) == i)
+                .cloned()
+                .collect();
+            Blockchain::new(VersaBlock::ShardBlock(ShardBlock::create(header, txs, 0)), config)
+        })
+        .collect();
+    Multichain::new(order_chain, shard_chains, config)
+}
+
+//a key whose address lives in the shard
+pub fn key_in_shard(config: &Configuration, shard_id: usize) -> Ed25519KeyPair {
+    loop {
+        let key = key_pair::random();
+        if config.get_shard_id_by_addr(&Address::from_key_pair(&key)) == shard_id {
+            return key;
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        configuration::{Configuration, ShardAssignment},
+        multichain::Multichain,
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        random::Random,
+        address::Address,
+    },
+};
+use super::common::{create_multichain, key_in_shard};
+use std::time::SystemTime;
+
+//extend the shard chain, returning whether the block is valid
+fn mine_shard_block(
+    multichain: &mut Multichain,
+    shard_id: usize,
+    txs: Vec<Transaction>
+) -> Result<bool, String> {
+    let parent = multichain.get_highest_shard_block(shard_id);
+    let order_parent = multichain.get_highest_order_block();
+    multichain.validate_shard_txs_with_parent(shard_id, &parent, &order_parent, &txs)?;
+    let header = BlockHeader::create(
+        shard_id,
+        order_parent,
+        parent,
+        H256::default(),
+        H256::default(),
+        H256::default(),
+        SystemTime::now(),
+    );
+    let block = VersaBlock::ShardBlock(ShardBlock::create(header, txs, 0));
+    multichain.insert_block_with_parent(block, &VersaHash::ShardHash(parent))
+}
+
+//extend the order chain with the newly confirmed shard blocks
+fn mine_order_block(multichain: &mut Multichain, coinbase_addr: Address) -> H256 {
+    let parent = multichain.get_highest_order_block();
+    let mut header = BlockHeader::create(
+        0,
+        parent,
+        H256::random(),
+        H256::default(),
+        H256::default(),
+        H256::default(),
+        SystemTime::now(),
+    );
+    header.set_coinbase_addr(coinbase_addr);
+    let block = VersaBlock::OrderBlock(OrderBlock::create(
+        header,
+        multichain.get_new_confirmed_shard_blocks(),
+        0,
+    ));
+    let hash = block.hash();
+    multichain.insert_block_with_parent(block, &VersaHash::OrderHash(parent)).unwrap();
+    hash
+}
+
+fn create_config() -> Configuration {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.k = 0;
+    config
+}
+
+#[test]
+fn test_cross_shard_accept() {
+    let config = create_config();
+    //alice lives in shard 0, bob in shard 1
+    let alice_key = key_in_shard(&config, 0);
+    let bob_key = key_in_shard(&config, 1);
+    let alice = Address::from_key_pair(&alice_key);
+    let bob = Address::from_key_pair(&bob_key);
+
+    let initial_tx = Transaction::create_initial_tx(&alice, 10);
+    let mut multichain = create_multichain(&config, std::slice::from_ref(&initial_tx));
+    let input_tx = Transaction::consume(
+        vec![(&initial_tx, 0)],
+        vec![&alice_key],
+        vec![(&bob, 10)],
+        TxFlag::Input,
+    ).unwrap();
+    //the source shard cannot finish a transfer which is not decided yet
+    let mut output_tx = input_tx.clone();
+    output_tx.flag = TxFlag::Output;
+    assert!(mine_shard_block(&mut multichain, 0, vec![output_tx.clone()]).is_err());
+    mine_shard_block(&mut multichain, 0, vec![input_tx.clone()]).unwrap();
+    assert!(multichain.get_shard_state(0).get_utxo(&(initial_tx.hash(), 0)).is_none());
+
+    //the destination waits until the input is anchored on the order chain
+    let mut accept_tx = input_tx.clone();
+    accept_tx.flag = TxFlag::Accept;
+    assert!(multichain.get_settlement_txs(1).is_empty());
+    assert!(mine_shard_block(&mut multichain, 1, vec![accept_tx.clone()]).is_err());
+    mine_order_block(&mut multichain, Address::default());
+    assert_eq!(multichain.get_settlement_txs(1), vec![accept_tx.clone()]);
+    mine_shard_block(&mut multichain, 1, vec![accept_tx.clone()]).unwrap();
+    assert!(multichain.get_settlement_txs(1).is_empty());
+    assert!(mine_shard_block(&mut multichain, 1, vec![accept_tx.clone()]).is_err());
+
+    //bob can spend the transfer in shard 1
+    let spend_tx = Transaction::consume(
+        vec![(&accept_tx, 0)],
+        vec![&bob_key],
+        vec![(&bob, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(multichain.get_shard_state(1).validate_tx(&spend_tx).is_ok());
+
+    //the source finishes once the acceptance is anchored
+    assert!(multichain.get_settlement_txs(0).is_empty());
+    mine_order_block(&mut multichain, Address::default());
+    assert_eq!(multichain.get_settlement_txs(0), vec![output_tx.clone()]);
+    mine_shard_block(&mut multichain, 0, vec![output_tx]).unwrap();
+    assert!(multichain.get_shard_state(0).get_locks().is_empty());
+    assert!(multichain.get_settlement_txs(0).is_empty());
+}
+
+#[test]
+fn test_cross_shard_refund() {
+    let config = create_config();
+    let alice_key = key_in_shard(&config, 0);
+    let bob_key = key_in_shard(&config, 1);
+    let alice = Address::from_key_pair(&alice_key);
+    let bob = Address::from_key_pair(&bob_key);
+
+    let initial_tx = Transaction::create_initial_tx(&alice, 10);
+    let mut multichain = create_multichain(&config, std::slice::from_ref(&initial_tx));
+    //a change output going back to shard 0 could never be accepted by shard 1
+    let change_tx = Transaction::consume(
+        vec![(&initial_tx, 0)],
+        vec![&alice_key],
+        vec![(&bob, 4), (&alice, 6)],
+        TxFlag::Input,
+    ).unwrap();
+    assert!(multichain.validate_new_tx(0, &change_tx).is_err());
+    assert!(mine_shard_block(&mut multichain, 0, vec![change_tx]).is_err());
+    //shard 1 cannot credit an empty output
+    let input_tx = Transaction::consume(
+        vec![(&initial_tx, 0)],
+        vec![&alice_key],
+        vec![(&bob, 10), (&bob, 0)],
+        TxFlag::Input,
+    ).unwrap();
+    mine_shard_block(&mut multichain, 0, vec![input_tx.clone()]).unwrap();
+    mine_order_block(&mut multichain, Address::default());
+
+    let mut reject_tx = input_tx.clone();
+    reject_tx.flag = TxFlag::Reject;
+    let mut accept_tx = input_tx.clone();
+    accept_tx.flag = TxFlag::Accept;
+    assert!(mine_shard_block(&mut multichain, 1, vec![accept_tx]).is_err());
+    assert_eq!(multichain.get_settlement_txs(1), vec![reject_tx.clone()]);
+    mine_shard_block(&mut multichain, 1, vec![reject_tx.clone()]).unwrap();
+    mine_order_block(&mut multichain, Address::default());
+
+    //the source refunds alice
+    assert_eq!(multichain.get_settlement_txs(0), vec![reject_tx.clone()]);
+    mine_shard_block(&mut multichain, 0, vec![reject_tx.clone()]).unwrap();
+    let refund = multichain.get_shard_state(0).get_utxo(&(reject_tx.hash(), 0)).unwrap();
+    assert_eq!(refund.output.receiver_addr, alice);
+    assert_eq!(refund.output.value, 10);
+    let spend_tx = Transaction::consume(
+        vec![(&reject_tx, 0)],
+        vec![&alice_key],
+        vec![(&alice, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(multichain.get_shard_state(0).validate_tx(&spend_tx).is_ok());
+    //and the transfer cannot be finished a second time
+    assert!(mine_shard_block(&mut multichain, 0, vec![reject_tx]).is_err());
+}
+
+#[test]
+fn test_shard_assignment() {
+    let low: Address = (&[0u8; 20]).into();
+    let high: Address = (&[255u8; 20]).into();
+    let mut middle_bytes = [0u8; 20];
+    middle_bytes[0] = 0x80;
+    middle_bytes[19] = 5;
+    let middle: Address = (&middle_bytes).into();
+    assert_eq!("hash-mod".parse::<ShardAssignment>(), Ok(ShardAssignment::HashMod));
+    assert_eq!("prefix-range".parse::<ShardAssignment>(), Ok(ShardAssignment::PrefixRange));
+    assert!("random".parse::<ShardAssignment>().is_err());
+    assert_eq!(ShardAssignment::HashMod.get_shard_id(middle.as_ref(), 4), 1);
+    assert_eq!(ShardAssignment::PrefixRange.get_shard_id(low.as_ref(), 4), 0);
+    assert_eq!(ShardAssignment::PrefixRange.get_shard_id(middle.as_ref(), 4), 2);
+    assert_eq!(ShardAssignment::PrefixRange.get_shard_id(high.as_ref(), 4), 3);
+
+    //transfers touching another shard must be flagged as cross-shard
+    for assignment in [ShardAssignment::HashMod, ShardAssignment::PrefixRange] {
+        let mut config = create_config();
+        config.shard_assignment = assignment;
+        let alice_key = key_in_shard(&config, 0);
+        let bob_key = key_in_shard(&config, 1);
+        let alice = Address::from_key_pair(&alice_key);
+        let bob = Address::from_key_pair(&bob_key);
+        let initial_tx = Transaction::create_initial_tx(&alice, 10);
+        let mut multichain = create_multichain(&config, std::slice::from_ref(&initial_tx));
+
+        let transfer = |flag: TxFlag| Transaction::consume(
+            vec![(&initial_tx, 0)],
+            vec![&alice_key],
+            vec![(&bob, 10)],
+            flag,
+        ).unwrap();
+        assert!(multichain.validate_new_tx(0, &transfer(TxFlag::Domestic)).is_err());
+        assert!(mine_shard_block(&mut multichain, 0, vec![transfer(TxFlag::Domestic)]).is_err());
+        assert!(multichain.validate_new_tx(0, &transfer(TxFlag::Input)).is_ok());
+        //another shard cannot spend alice's outputs
+        let local = Transaction::consume(
+            vec![(&initial_tx, 0)],
+            vec![&alice_key],
+            vec![(&alice, 10)],
+            TxFlag::Domestic,
+        ).unwrap();
+        assert!(multichain.validate_new_tx(0, &local).is_ok());
+        assert!(multichain.validate_new_tx(1, &local).is_err());
+    }
+}
+
+#[test]
+fn test_coinbase_rewards() {
+    let mut config = create_config();
+    config.block_reward = 50;
+    config.order_reward = 30;
+    config.coinbase_maturity = 2;
+    let alice_key = key_in_shard(&config, 0);
+    let miner_key = key_in_shard(&config, 0);
+    let alice = Address::from_key_pair(&alice_key);
+    let miner = Address::from_key_pair(&miner_key);
+    let initial_tx = Transaction::create_initial_tx(&alice, 10);
+    let mut multichain = create_multichain(&config, std::slice::from_ref(&initial_tx));
+
+    //value is only minted by the genesis allocation
+    let minting_tx = Transaction::create_initial_tx(&alice, 5);
+    assert!(mine_shard_block(&mut multichain, 0, vec![minting_tx]).is_err());
+
+    //the miner takes the subsidy and the fee alice leaves
+    let spend_tx = Transaction::consume(
+        vec![(&initial_tx, 0)],
+        vec![&alice_key],
+        vec![(&alice, 7)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let parent = multichain.get_highest_shard_block(0);
+    let reward_tx = multichain
+        .get_shard_state(0)
+        .create_block_reward(std::slice::from_ref(&spend_tx), &miner)
+        .unwrap();
+    assert_eq!(reward_tx.outputs[0].value, 53);
+    let greedy_tx = Transaction::create_coinbase_tx(parent, &miner, 54);
+    assert!(mine_shard_block(&mut multichain, 0, vec![greedy_tx, spend_tx.clone()]).is_err());
+    assert!(mine_shard_block(&mut multichain, 0, vec![spend_tx.clone(), reward_tx.clone()]).is_err());
+    mine_shard_block(&mut multichain, 0, vec![reward_tx.clone(), spend_tx]).unwrap();
+
+    //the reward matures after the configured depth
+    let claim_tx = Transaction::consume(
+        vec![(&reward_tx, 0)],
+        vec![&miner_key],
+        vec![(&miner, 53)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(multichain.validate_new_tx(0, &claim_tx).is_err());
+    mine_shard_block(&mut multichain, 0, vec![]).unwrap();
+    assert!(multichain.validate_new_tx(0, &claim_tx).is_ok());
+
+    //the reward of an order block is paid once in the shard of its miner
+    let order_hash = mine_order_block(&mut multichain, miner);
+    let order_reward_tx = Transaction::create_coinbase_tx(order_hash, &miner, 30);
+    assert_eq!(multichain.get_settlement_txs(0), vec![order_reward_tx.clone()]);
+    assert!(multichain.get_settlement_txs(1).is_empty());
+    let forged_tx = Transaction::create_coinbase_tx(H256::random(), &miner, 30);
+    assert!(mine_shard_block(&mut multichain, 0, vec![forged_tx]).is_err());
+    mine_shard_block(&mut multichain, 0, vec![order_reward_tx.clone()]).unwrap();
+    assert!(multichain.get_settlement_txs(0).is_empty());
+    assert!(mine_shard_block(&mut multichain, 0, vec![order_reward_tx]).is_err());
+
+    //a longer fork of the order chain brings its own rewards
+    let order_genesis = multichain.all_blocks_in_longest_order_chain()[0];
+    let mut fork = vec![];
+    let mut fork_parent = order_genesis;
+    for _ in 0..2 {
+        let mut header = BlockHeader::create(
+            0,
+            fork_parent,
+            H256::random(),
+            H256::default(),
+            H256::default(),
+            H256::default(),
+            SystemTime::now(),
+        );
+        header.set_coinbase_addr(miner);
+        let block = VersaBlock::OrderBlock(OrderBlock::create(header, vec![], 0));
+        multichain.insert_block_with_parent(block.clone(), &VersaHash::OrderHash(fork_parent)).unwrap();
+        fork_parent = block.hash();
+        fork.push(block.hash());
+    }
+    let fork_rewards: Vec<Transaction> = fork
+        .iter()
+        .map(|x| Transaction::create_coinbase_tx(*x, &miner, 30))
+        .collect();
+    assert_eq!(multichain.get_settlement_txs(0), fork_rewards);
+    //a reward is paid after the order block it rewards only
+    let shard_tip = multichain.get_highest_shard_block(0);
+    assert!(multichain
+        .validate_shard_txs_with_parent(0, &shard_tip, &order_hash, &fork_rewards[..1])
+        .is_err());
+    assert!(multichain
+        .validate_shard_txs_with_parent(0, &shard_tip, &fork[1], &fork_rewards[..1])
+        .is_ok());
+}
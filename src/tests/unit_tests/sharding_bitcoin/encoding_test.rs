@@ -0,0 +1,131 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        network::message::Message,
+        transaction::{Transaction, TxFlag, UtxoInput, UtxoOutput},
+    },
+    types::{
+        hash::{H256, Hashable},
+        encoding::{self, ENCODING_VERSION},
+        random::Random,
+    },
+};
+use std::time::{Duration, UNIX_EPOCH};
+
+fn fixed_tx() -> Transaction {
+    Transaction {
+        inputs: vec![UtxoInput {
+            sender_addr: (&[1u8; 20]).into(),
+            tx_hash: (&[2u8; 32]).into(),
+            value: 5,
+            index: 1,
+            public_key_ref: vec![0xdd],
+            sig_ref: vec![0xaa, 0xbb],
+        }],
+        outputs: vec![UtxoOutput {
+            receiver_addr: (&[3u8; 20]).into(),
+            value: 5,
+        }],
+        flag: TxFlag::Domestic,
+    }
+}
+
+fn fixed_header() -> BlockHeader {
+    let mut header = BlockHeader::create(
+        1,
+        (&[4u8; 32]).into(),
+        (&[5u8; 32]).into(),
+        (&[6u8; 32]).into(),
+        (&[7u8; 32]).into(),
+        (&[8u8; 32]).into(),
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_000),
+    );
+    header.set_coinbase_addr((&[9u8; 20]).into());
+    header
+}
+
+#[test]
+fn test_encoding_vectors() {
+    let expected = [
+        "01".to_string(),
+        //one input
+        "00000001".to_string(),
+        "01".repeat(20),
+        "02".repeat(32),
+        "00000005".to_string(),
+        "00000001".to_string(),
+        "00000001dd".to_string(),
+        "00000002aabb".to_string(),
+        //one output
+        "00000001".to_string(),
+        "03".repeat(20),
+        "00000005".to_string(),
+        //domestic flag
+        "01".to_string(),
+    ].concat();
+    assert_eq!(hex::encode(encoding::serialize(&fixed_tx())), expected);
+    assert_eq!(
+        fixed_tx().hash().to_string(),
+        "a2cfe8c25ef21e1c5e7b82c048ef293f523665b6bd6a8c764daa30788e204dc2"
+    );
+
+    let expected = [
+        "01".to_string(),
+        "00000001".to_string(),
+        "04".repeat(32),
+        "05".repeat(32),
+        "06".repeat(32),
+        "07".repeat(32),
+        "08".repeat(32),
+        "0000018bcfe56800".to_string(),
+        "09".repeat(20),
+    ].concat();
+    assert_eq!(hex::encode(encoding::serialize(&fixed_header())), expected);
+    assert_eq!(
+        fixed_header().hash().to_string(),
+        "7d1dcc2f5dfb13ccaf4c9951af3d8a3ce21d8dacd0d8a13802f1bace129e235f"
+    );
+}
+
+#[test]
+fn test_encoding_round_trip() {
+    let txs = vec![fixed_tx(), Transaction::random()];
+    let shard_block = VersaBlock::ShardBlock(ShardBlock::create(fixed_header(), txs.clone(), 3));
+    let order_block = VersaBlock::OrderBlock(OrderBlock::create(fixed_header(), vec![H256::random()], 4));
+    for block in [shard_block, order_block] {
+        let decoded: VersaBlock = encoding::deserialize(&encoding::serialize(&block)).unwrap();
+        assert_eq!(decoded, block);
+        assert_eq!(decoded.hash(), block.hash());
+    }
+
+    let msg = Message::RejectedBlocks(vec![(VersaHash::OrderHash(H256::random()), String::from("invalid"))]);
+    let decoded: Message = encoding::deserialize(&encoding::serialize(&msg)).unwrap();
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", msg));
+    let msg = Message::Transactions(txs.clone());
+    let decoded: Message = encoding::deserialize(&encoding::serialize(&msg)).unwrap();
+    assert!(matches!(decoded, Message::Transactions(decoded_txs) if decoded_txs == txs));
+}
+
+#[test]
+fn test_encoding_rejects_malformed() {
+    let bytes = encoding::serialize(&fixed_tx());
+    assert!(encoding::deserialize::<Transaction>(&bytes).is_ok());
+    //unknown version
+    let mut other_version = bytes.clone();
+    other_version[0] = ENCODING_VERSION + 1;
+    assert!(encoding::deserialize::<Transaction>(&other_version).is_err());
+    //truncated and trailing bytes
+    assert!(encoding::deserialize::<Transaction>(&bytes[..bytes.len() - 1]).is_err());
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(encoding::deserialize::<Transaction>(&trailing).is_err());
+    //unknown flag
+    let mut unknown_flag = bytes;
+    *unknown_flag.last_mut().unwrap() = 9;
+    assert!(encoding::deserialize::<Transaction>(&unknown_flag).is_err());
+}
@@ -0,0 +1,110 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{Content, versa_block::VersaBlock},
+        blockchain::Blockchain,
+        configuration::Configuration,
+        genesis::{GenesisSpec, Allocation},
+        multichain::Multichain,
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+    },
+};
+use super::common::key_in_shard;
+
+fn create_config() -> Configuration {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config
+}
+
+#[test]
+fn test_genesis_spec() {
+    let config = create_config();
+    let alice_key = key_in_shard(&config, 0);
+    let alice = Address::from_key_pair(&alice_key);
+    let bob = Address::from_key_pair(&key_in_shard(&config, 1));
+    let json = format!(
+        r#"{{
+            "chain_id": 7,
+            "order_target": "{}",
+            "shard_target": "{}",
+            "allocations": [
+                [{{"address": "{}", "value": 10}}, {{"address": "{}", "value": 5}}],
+                [{{"address": "{}", "value": 20}}]
+            ]
+        }}"#,
+        "00ff".repeat(16),
+        "0fff".repeat(16),
+        alice,
+        alice,
+        bob,
+    );
+    let spec: GenesisSpec = serde_json::from_str(&json).unwrap();
+    assert!(spec.validate(&config).is_ok());
+    let mut order_target = [0xffu8; 32];
+    order_target.iter_mut().step_by(2).for_each(|x| *x = 0);
+    assert_eq!(spec.get_order_target().unwrap(), H256::from(order_target));
+
+    //an allocation must belong to the shard minting it
+    let mut wrong_shard = spec.clone();
+    wrong_shard.allocations.swap(0, 1);
+    assert!(wrong_shard.validate(&config).is_err());
+    let mut too_many = spec.clone();
+    too_many.allocations.push(vec![]);
+    assert!(too_many.validate(&config).is_err());
+    let mut bad_target = spec.clone();
+    bad_target.shard_target = String::from("00");
+    assert!(bad_target.validate(&config).is_err());
+
+    //the genesis of every shard mints its allocations
+    let order_chain = Blockchain::new(spec.get_order_genesis(), &config);
+    let shard_chains: Vec<Blockchain> = (0..config.shard_num)
+        .map(|i| Blockchain::new(spec.get_shard_genesis(i), &config))
+        .collect();
+    let multichain = Multichain::new(order_chain, shard_chains, &config);
+    let allocation_tx = spec.get_allocation_tx(0).unwrap();
+    assert_eq!(allocation_tx.outputs.len(), 2);
+    assert_eq!(multichain.get_shard_state(0).get_utxo_size(), 2);
+    assert_eq!(multichain.get_shard_state(1).get_utxo_size(), 1);
+    let spend_tx = Transaction::consume(
+        vec![(&allocation_tx, 0)],
+        vec![&alice_key],
+        vec![(&alice, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(multichain.validate_new_tx(0, &spend_tx).is_ok());
+    //a shard without allocations starts empty
+    let mut empty = spec.clone();
+    empty.allocations.truncate(1);
+    assert!(empty.get_allocation_tx(1).is_none());
+    match empty.get_shard_genesis(1) {
+        VersaBlock::ShardBlock(block) => {
+            assert!(block.get_txs().is_empty());
+        }
+        _ => panic!("A shard genesis is a shard block"),
+    }
+}
+
+#[test]
+fn test_genesis_hash() {
+    let config = create_config();
+    let alice = Address::from_key_pair(&key_in_shard(&config, 0));
+    let mut spec = GenesisSpec::new(1, &config);
+    let genesis_hash = spec.get_genesis_hash(config.shard_num);
+    assert_eq!(GenesisSpec::new(1, &config).get_genesis_hash(config.shard_num), genesis_hash);
+    assert_eq!(spec.get_order_genesis().hash(), GenesisSpec::new(1, &config).get_order_genesis().hash());
+    //peers of another chain, with other targets or allocations build other genesis blocks
+    assert_ne!(GenesisSpec::new(2, &config).get_genesis_hash(config.shard_num), genesis_hash);
+    let mut other_target = spec.clone();
+    other_target.order_target = "0f".repeat(32);
+    assert_ne!(other_target.get_genesis_hash(config.shard_num), genesis_hash);
+    spec.allocations = vec![vec![Allocation {
+        address: alice.to_string(),
+        value: 1,
+    }]];
+    assert_ne!(spec.get_genesis_hash(config.shard_num), genesis_hash);
+    assert_ne!(spec.get_shard_genesis(0).hash(), spec.get_shard_genesis(1).hash());
+}
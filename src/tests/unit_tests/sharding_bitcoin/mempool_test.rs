@@ -0,0 +1,324 @@
+use crate::{
+    sharding_bitcoin::{
+        configuration::Configuration,
+        mempool::{Mempool, MempoolEntry},
+        state::UtxoState,
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        key_pair,
+        address::Address,
+    },
+};
+use ring::signature::Ed25519KeyPair;
+
+#[test]
+fn test_fee_rate_mempool() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+
+    let config = Configuration::new();
+    let mut state = UtxoState::new(0, &config);
+    let fund1 = Transaction::create_initial_tx(&user1, 100);
+    let fund2 = Transaction::create_initial_tx(&user2, 100);
+    let blk1: H256 = (&[11u8; 32]).into();
+    state.apply_block(&blk1, &[fund1.clone(), fund2.clone()]).unwrap();
+
+    //the fee is what the inputs leave behind
+    let low = Transaction::consume(
+        vec![(&fund1, 0)],
+        vec![&key1],
+        vec![(&user2, 99)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let high = Transaction::consume(
+        vec![(&fund2, 0)],
+        vec![&key2],
+        vec![(&user1, 90)],
+        TxFlag::Domestic,
+    ).unwrap();
+    //spends the same output as the low paying one, for a higher fee
+    let conflict = Transaction::consume(
+        vec![(&fund1, 0)],
+        vec![&key1],
+        vec![(&user2, 95)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert_eq!(state.get_fee(&low), Some(1));
+    assert_eq!(state.get_fee(&high), Some(10));
+    assert_eq!(state.get_fee(&conflict), Some(5));
+    let unknown = Transaction::consume(
+        vec![(&low, 0)],
+        vec![&key2],
+        vec![(&user1, 99)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert_eq!(state.get_fee(&unknown), None);
+
+    let mut mempool = Mempool::new(&config);
+    for tx in [&low, &high] {
+        assert!(mempool.insert_tx(tx.clone(), state.get_fee(tx).unwrap()).is_ok());
+    }
+    assert!(mempool.insert_tx(low.clone(), 1).is_err());
+    assert_eq!(mempool.get_stats().duplicate, 1);
+    //without replace-by-fee the first spend of an output stays
+    assert!(mempool.insert_tx(conflict.clone(), 5).is_err());
+    assert_eq!(mempool.get_stats().conflict, 1);
+    assert_eq!(mempool.get_spender(&(fund1.hash(), 0)), Some(low.hash()));
+    assert_eq!(mempool.get_size(), 2);
+
+    //the best paying transactions come first
+    let txs = mempool.get_txs(10).unwrap();
+    let hashes: Vec<H256> = txs.iter().map(|x| x.hash()).collect();
+    assert_eq!(hashes, vec![high.hash(), low.hash()]);
+    assert_eq!(mempool.get_size(), 0);
+    assert_eq!(mempool.get_spender(&(fund1.hash(), 0)), None);
+
+    //a limited selection reports what is left
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(low.clone(), 1).unwrap();
+    mempool.insert_tx(high.clone(), 10).unwrap();
+    let txs = mempool.get_txs(1).unwrap_err();
+    assert_eq!(txs[0].hash(), high.hash());
+    assert_eq!(mempool.pop_one_tx().unwrap().hash(), low.hash());
+    assert!(mempool.pop_one_tx().is_none());
+}
+
+#[test]
+fn test_mempool_limits() {
+    let key: Ed25519KeyPair = key_pair::random();
+    let user = Address::from_key_pair(&key);
+    //transactions of the same size, told apart by their value
+    let txs: Vec<Transaction> = (1..=4)
+        .map(|i| Transaction::create_initial_tx(&user, i))
+        .collect();
+    let size = txs[0].get_mem_size();
+
+    let mut config = Configuration::new();
+    config.mempool_max_txs = 2;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(txs[0].clone(), 10).unwrap();
+    mempool.insert_tx(txs[1].clone(), 20).unwrap();
+    //a full mempool turns away a transaction paying no more than the lowest one
+    assert!(mempool.insert_tx(txs[2].clone(), 10).is_err());
+    //and evicts the lowest one for a better paying transaction
+    assert!(mempool.insert_tx(txs[3].clone(), 30).is_ok());
+    assert!(!mempool.check(&txs[0].hash()));
+    assert_eq!(mempool.get_size(), 2);
+    assert_eq!(mempool.get_bytes(), 2 * size);
+    let stats = mempool.get_stats();
+    assert_eq!((stats.low_fee, stats.evicted), (1, 1));
+
+    //the byte limit evicts as many transactions as needed
+    let mut config = Configuration::new();
+    config.mempool_max_bytes = 2 * size;
+    let mut mempool = Mempool::new(&config);
+    let large = Transaction {
+        outputs: vec![txs[0].outputs[0].clone(); 2],
+        ..txs[0].clone()
+    };
+    assert!(large.get_mem_size() <= 2 * size);
+    mempool.insert_tx(txs[0].clone(), 10).unwrap();
+    mempool.insert_tx(txs[1].clone(), 20).unwrap();
+    assert!(mempool.insert_tx(large.clone(), 100).is_ok());
+    assert_eq!(mempool.get_size(), 1);
+    assert_eq!(mempool.get_stats().evicted, 2);
+    let huge = Transaction {
+        outputs: vec![txs[0].outputs[0].clone(); 100],
+        ..txs[0].clone()
+    };
+    assert!(mempool.insert_tx(huge, 1000).is_err());
+    assert_eq!(mempool.get_stats().too_large, 1);
+
+    //transactions pending for longer than the expiry are dropped
+    let mut config = Configuration::new();
+    config.mempool_expiry = 60;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(txs[0].clone(), 10).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert_eq!(mempool.expire(now), 0);
+    assert_eq!(mempool.expire(now + 120), 1);
+    assert_eq!(mempool.get_size(), 0);
+    assert_eq!(mempool.get_bytes(), 0);
+    assert_eq!(mempool.get_stats().expired, 1);
+}
+
+#[test]
+fn test_replace_by_fee() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+
+    let mut config = Configuration::new();
+    config.mempool_rbf = true;
+    let fund = Transaction::create_initial_tx(&user1, 100);
+    let spend = |value: usize| Transaction::consume(
+        vec![(&fund, 0)],
+        vec![&key1],
+        vec![(&user2, value)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let parent = spend(99);
+    //spends the output of the pending parent
+    let child = Transaction::consume(
+        vec![(&parent, 0)],
+        vec![&key2],
+        vec![(&user1, 97)],
+        TxFlag::Domestic,
+    ).unwrap();
+
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(parent.clone(), 1).unwrap();
+    mempool.insert_tx(child.clone(), 2).unwrap();
+    //a replacement must pay more than the parent and its child together
+    assert!(mempool.insert_tx(spend(98), 2).is_err());
+    assert!(mempool.insert_tx(spend(97), 3).is_err());
+    assert_eq!(mempool.get_stats().conflict, 2);
+    let replacement = spend(96);
+    assert!(mempool.insert_tx(replacement.clone(), 4).is_ok());
+    assert!(!mempool.check(&parent.hash()));
+    assert!(!mempool.check(&child.hash()));
+    assert_eq!(mempool.get_stats().replaced, 2);
+    assert_eq!(mempool.get_spender(&(fund.hash(), 0)), Some(replacement.hash()));
+    assert_eq!(mempool.get_spender(&(parent.hash(), 0)), None);
+    assert_eq!(mempool.get_size(), 1);
+    assert_eq!(mempool.get_bytes(), replacement.get_mem_size());
+}
+
+#[test]
+fn test_child_pays_for_parent() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+
+    let mut config = Configuration::new();
+    let mut state = UtxoState::new(0, &config);
+    let fund1 = Transaction::create_initial_tx(&user1, 100);
+    let fund2 = Transaction::create_initial_tx(&user2, 100);
+    let blk1: H256 = (&[11u8; 32]).into();
+    state.apply_block(&blk1, &[fund1.clone(), fund2.clone()]).unwrap();
+
+    let parent = Transaction::consume(
+        vec![(&fund1, 0)],
+        vec![&key1],
+        vec![(&user2, 50), (&user2, 49)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let other = Transaction::consume(
+        vec![(&fund2, 0)],
+        vec![&key2],
+        vec![(&user1, 95)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let spend_parent = |index: usize, value: usize| Transaction::consume(
+        vec![(&parent, index)],
+        vec![&key2],
+        vec![(&user1, value)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let child = spend_parent(0, 30);
+    let sibling = spend_parent(1, 48);
+
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(parent.clone(), state.get_fee(&parent).unwrap()).unwrap();
+    mempool.insert_tx(other.clone(), state.get_fee(&other).unwrap()).unwrap();
+    //the child spends an output which only exists in the mempool
+    let ancestors = mempool.get_ancestor_txs(&child);
+    assert_eq!(ancestors, vec![parent.clone()]);
+    assert!(state.validate_tx(&child).is_err());
+    assert!(state.validate_tx_with_ancestors(&child, &ancestors).is_ok());
+    assert_eq!(state.get_fee(&child), None);
+    assert_eq!(state.get_fee_with_ancestors(&child, &ancestors), Some(20));
+    mempool.insert_tx(child.clone(), 20).unwrap();
+
+    //the package of the parent and its child outbids the other transaction, 
+    //and the parent comes first
+    let txs = mempool.get_txs(10).unwrap();
+    let hashes: Vec<H256> = txs.iter().map(|x| x.hash()).collect();
+    assert_eq!(hashes, vec![parent.hash(), child.hash(), other.hash()]);
+    //a package larger than the room left is not split
+    for tx in txs.iter() {
+        mempool.insert_tx(tx.clone(), state.get_fee_with_ancestors(tx, &txs).unwrap()).unwrap();
+    }
+    let txs = mempool.get_txs(1).unwrap_err();
+    assert_eq!(txs, vec![other.clone()]);
+    assert_eq!(mempool.pop_one_tx(), Some(parent.clone()));
+    assert_eq!(mempool.pop_one_tx(), Some(child.clone()));
+
+    //a full mempool keeps the parent of a well paying child
+    config.mempool_max_txs = 2;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(parent.clone(), 1).unwrap();
+    mempool.insert_tx(child.clone(), 20).unwrap();
+    assert!(mempool.insert_tx(other.clone(), 5).is_err());
+    assert_eq!(mempool.get_stats().low_fee, 1);
+
+    //chains of pending transactions are limited
+    let mut config = Configuration::new();
+    config.mempool_max_ancestors = 1;
+    config.mempool_max_descendants = 1;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(parent.clone(), 1).unwrap();
+    mempool.insert_tx(child.clone(), 20).unwrap();
+    assert!(mempool.insert_tx(sibling.clone(), 1).is_err());
+    let grandchild = Transaction::consume(
+        vec![(&child, 0)],
+        vec![&key1],
+        vec![(&user2, 29)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(mempool.insert_tx(grandchild.clone(), 1).is_err());
+    assert_eq!(mempool.get_stats().chain_limit, 2);
+
+    //an expired parent takes its descendants along
+    let mut config = Configuration::new();
+    config.mempool_expiry = 60;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(parent.clone(), 1).unwrap();
+    mempool.insert_tx(child.clone(), 20).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert_eq!(mempool.expire(now + 120), 2);
+    assert_eq!(mempool.get_size(), 0);
+}
+
+#[test]
+fn test_restore_entries() {
+    let key: Ed25519KeyPair = key_pair::random();
+    let user = Address::from_key_pair(&key);
+    let txs: Vec<Transaction> = (1..=4)
+        .map(|i| Transaction::create_initial_tx(&user, i))
+        .collect();
+    let mut config = Configuration::new();
+    config.mempool_max_txs = 2;
+    let mut mempool = Mempool::new(&config);
+    mempool.insert_tx(txs[0].clone(), 10).unwrap();
+    mempool.insert_tx(txs[1].clone(), 20).unwrap();
+
+    //an entry taken for a block comes back as it entered
+    let entry = mempool.get_entries(1).unwrap_err().pop().unwrap();
+    assert_eq!(entry.tx, txs[1]);
+    let entry = MempoolEntry { time: entry.time - 100, ..entry };
+    mempool.restore(entry.clone()).unwrap();
+    let restored = mempool.get_entry(&txs[1].hash()).unwrap();
+    assert_eq!((restored.fee, restored.time), (20, entry.time));
+
+    //its rejections are not counted, it is not a new transaction
+    assert!(mempool.restore(entry.clone()).is_err());
+    mempool.get_entries(1).unwrap_err();
+    mempool.insert_tx(txs[2].clone(), 30).unwrap();
+    mempool.insert_tx(txs[3].clone(), 40).unwrap();
+    assert!(mempool.restore(entry).is_err());
+    let stats = mempool.get_stats();
+    assert_eq!((stats.duplicate, stats.low_fee, stats.evicted), (0, 0, 1));
+}
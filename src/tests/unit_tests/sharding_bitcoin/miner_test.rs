@@ -0,0 +1,183 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            Block,
+            BlockHeader,
+            Content,
+            Info,
+            ShardBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        configuration::Configuration,
+        mempool::Mempool,
+        miner::{
+            self,
+            MinerMessage,
+            solver::{self, MiningState},
+        },
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+    },
+};
+use super::common::{create_multichain, key_in_shard};
+use crossbeam::channel::{unbounded, Receiver};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+fn gen_template(shard_parent: H256, shard_target: H256) -> Block {
+    Block::construct(
+        0,
+        H256::default(),
+        shard_parent,
+        (&[0u8; 32]).into(),
+        shard_target,
+        SystemTime::now(),
+        Address::default(),
+        vec![],
+        vec![],
+    )
+}
+
+#[test]
+fn test_mining_threads() {
+    let state = Arc::new(MiningState::new(4));
+    let (finished_sender, finished_receiver) = unbounded();
+    solver::spawn(4, &state, &finished_sender);
+
+    //no hash is below a zero target, the threads keep trying
+    let hard_parent: H256 = (&[1u8; 32]).into();
+    let hard_id = state.publish(gen_template(hard_parent, (&[0u8; 32]).into()));
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(state.is_current(hard_id));
+    assert!(finished_receiver.try_recv().is_err());
+
+    //a new template interrupts them, and only one block is handed in for it
+    let easy_parent: H256 = (&[2u8; 32]).into();
+    let easy_template = gen_template(easy_parent, (&[255u8; 32]).into());
+    let easy_id = state.publish(easy_template.clone());
+    let MinerMessage::VersaBlk(block) = finished_receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    match block {
+        VersaBlock::ShardBlock(shard_block) => {
+            assert_eq!(shard_block.get_shard_parent(), easy_parent);
+            assert_eq!(shard_block.get_timestamp(), easy_template.get_header().get_timestamp());
+        }
+        _ => panic!("A zero order target seals no order block"),
+    }
+    //the job was taken by the thread which found the block
+    assert!(!state.is_current(easy_id));
+    assert!(!state.retire(easy_id));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(finished_receiver.try_recv().is_err());
+
+    let stats = state.get_stats();
+    assert_eq!(stats.threads, 4);
+    assert_eq!(stats.blocks, 1);
+    assert!(stats.hashes > 0);
+    state.shutdown();
+}
+
+//the next block handed in by the miner, a shard block as the order target is zero
+fn recv_shard_block(receiver: &Receiver<MinerMessage>) -> VersaBlock {
+    let MinerMessage::VersaBlk(block) = receiver
+        .recv_timeout(Duration::from_secs(10))
+        .unwrap();
+    assert!(matches!(block, VersaBlock::ShardBlock(_)));
+    block
+}
+
+#[test]
+fn test_miner_update() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    config.order_diff = (&[0u8; 32]).into();
+    config.miner_threads = 2;
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let mempool = Arc::new(Mutex::new(Mempool::new(&config)));
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+
+    let (miner_ctx, miner, finished_receiver) = miner::new(&multichain, &mempool, &config);
+    miner_ctx.start();
+    miner.start(0);
+    let first = recv_shard_block(&finished_receiver);
+    assert_eq!(first.get_parent(), genesis);
+
+    //without a notification the template is not rebuilt
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(finished_receiver.try_recv().is_err());
+
+    //told about the new tip, the miner extends it
+    multichain
+        .lock()
+        .unwrap()
+        .insert_block_with_parent(first.clone(), &VersaHash::ShardHash(genesis))
+        .unwrap();
+    miner.update();
+    let second = recv_shard_block(&finished_receiver);
+    assert_eq!(second.get_parent(), first.hash());
+    miner.exit();
+}
+
+#[test]
+fn test_mined_block_overtaken() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    config.order_diff = (&[0u8; 32]).into();
+    config.miner_threads = 1;
+    config.block_size = 10;
+    let key = key_in_shard(&config, 0);
+    let user = Address::from_key_pair(&key);
+    let fund = Transaction::create_initial_tx(&user, 100);
+    let spend = Transaction::consume(vec![(&fund, 0)], vec![&key], vec![(&user, 90)], TxFlag::Domestic)
+        .unwrap();
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, std::slice::from_ref(&fund))));
+    let mempool = Arc::new(Mutex::new(Mempool::new(&config)));
+    mempool.lock().unwrap().insert_tx(spend.clone(), 10).unwrap();
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+
+    let (miner_ctx, miner, finished_receiver) = miner::new(&multichain, &mempool, &config);
+    miner_ctx.start();
+    miner.start(0);
+    let mined = match recv_shard_block(&finished_receiver) {
+        VersaBlock::ShardBlock(shard_block) => shard_block,
+        _ => unreachable!(),
+    };
+    miner.exit();
+    assert_eq!(mined.get_txs(), vec![spend.clone()]);
+    assert!(!mempool.lock().unwrap().check(&spend.hash()));
+
+    //meanwhile, a longer chain of peer blocks took the tip
+    let mut multichain = multichain.lock().unwrap();
+    let mut parent = genesis;
+    for _ in 0..2 {
+        let header = BlockHeader::create(
+            0,
+            multichain.get_highest_order_block(),
+            parent,
+            H256::default(),
+            config.order_diff,
+            config.block_diff,
+            SystemTime::now(),
+        );
+        let block = VersaBlock::ShardBlock(ShardBlock::create(header, vec![], 0));
+        multichain.insert_block_with_parent(block.clone(), &VersaHash::ShardHash(parent)).unwrap();
+        parent = block.hash();
+    }
+
+    //the mined block lands on a side fork, its transaction stays pending
+    let mined_hash = mined.hash();
+    let mut mempool = mempool.lock().unwrap();
+    assert!(multichain.insert_mined_shard_block(mined, &mut mempool).is_ok());
+    assert_eq!(multichain.get_highest_shard_block(0), parent);
+    assert_ne!(multichain.get_highest_shard_block(0), mined_hash);
+    assert!(mempool.check(&spend.hash()));
+    assert_eq!(mempool.get_entry(&spend.hash()).unwrap().fee, 10);
+}
@@ -0,0 +1,9 @@
+pub mod common;
+pub mod validator_test;
+pub mod state_test;
+pub mod cross_shard_test;
+pub mod multichain_test;
+pub mod encoding_test;
+pub mod genesis_test;
+pub mod mempool_test;
+pub mod miner_test;
@@ -0,0 +1,374 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        blockchain::Reorg,
+        configuration::Configuration,
+        mempool::Mempool,
+        multichain::{Multichain, LedgerPosition},
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+        key_pair,
+        random::Random,
+    },
+};
+use super::common::create_multichain;
+use std::time::{Duration, SystemTime};
+
+fn gen_shard_block(shard_id: usize, shard_parent: H256, txs: Vec<Transaction>) -> VersaBlock {
+    let header = BlockHeader::create(
+        shard_id,
+        H256::random(),
+        shard_parent,
+        H256::default(),
+        H256::default(),
+        H256::default(),
+        SystemTime::now(),
+    );
+    VersaBlock::ShardBlock(ShardBlock::create(header, txs, 0))
+}
+
+fn gen_order_block(order_parent: H256, confirmed_shard_blocks: Vec<H256>) -> VersaBlock {
+    let header = BlockHeader::create(
+        0,
+        order_parent,
+        H256::random(),
+        H256::default(),
+        H256::default(),
+        H256::default(),
+        SystemTime::now(),
+    );
+    VersaBlock::OrderBlock(OrderBlock::create(header, confirmed_shard_blocks, 0))
+}
+
+#[test]
+fn test_global_ledger() {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.k = 0;
+    let mut multichain = create_multichain(&config, &[]);
+    let genesis_0 = multichain.get_highest_shard_block(0);
+    let genesis_1 = multichain.get_highest_shard_block(1);
+    let order_genesis = multichain.get_highest_order_block();
+
+    let txs: Vec<Transaction> = (0..4).map(|_| Transaction::random()).collect();
+    let blk_0a = gen_shard_block(0, genesis_0, vec![txs[0].clone(), txs[1].clone()]);
+    let blk_0b = gen_shard_block(0, blk_0a.hash(), vec![txs[2].clone()]);
+    let blk_1a = gen_shard_block(1, genesis_1, vec![txs[3].clone()]);
+    for (block, parent) in [(&blk_0a, genesis_0), (&blk_0b, blk_0a.hash()), (&blk_1a, genesis_1)] {
+        multichain.insert_block_with_parent(block.clone(), &VersaHash::ShardHash(parent)).unwrap();
+    }
+
+    //the second order block references blk_0a again, which is ignored
+    let order_1 = gen_order_block(order_genesis, vec![blk_1a.hash(), blk_0a.hash()]);
+    let order_2 = gen_order_block(order_1.hash(), vec![blk_0a.hash(), blk_0b.hash()]);
+    multichain.insert_block_with_parent(order_1.clone(), &VersaHash::OrderHash(order_genesis)).unwrap();
+    multichain.insert_block_with_parent(order_2.clone(), &VersaHash::OrderHash(order_1.hash())).unwrap();
+
+    let ledger = multichain.get_global_ledger();
+    let tx_hashes: Vec<H256> = ledger.iter().map(|x| x.tx_hash).collect();
+    let expected: Vec<H256> = [0, 1, 3, 2].iter().map(|i| txs[*i].hash()).collect();
+    assert_eq!(tx_hashes, expected);
+    assert_eq!(ledger[2].position, LedgerPosition {
+        order_height: 1,
+        shard_id: 1,
+        shard_height: 1,
+        tx_index: 0,
+    });
+    assert_eq!(ledger[3].position.order_height, 2);
+    assert!(ledger.windows(2).all(|x| x[0].position < x[1].position));
+
+    let page = multichain.get_global_ledger_page(1, 2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].tx_hash, txs[1].hash());
+    assert!(multichain.get_global_ledger_page(4, 10).is_empty());
+    assert_eq!(multichain.get_global_ledger_size(), 4);
+
+    //blk_1a leaves the longest chain of its shard, and the ledger with it
+    let blk_1b = gen_shard_block(1, genesis_1, vec![]);
+    let blk_1c = gen_shard_block(1, blk_1b.hash(), vec![]);
+    multichain.insert_block_with_parent(blk_1b.clone(), &VersaHash::ShardHash(genesis_1)).unwrap();
+    multichain.insert_block_with_parent(blk_1c.clone(), &VersaHash::ShardHash(blk_1b.hash())).unwrap();
+    let tx_hashes: Vec<H256> = multichain.get_global_ledger().iter().map(|x| x.tx_hash).collect();
+    let expected: Vec<H256> = [0, 1, 2].iter().map(|i| txs[*i].hash()).collect();
+    assert_eq!(tx_hashes, expected);
+    assert_eq!(multichain.get_global_ledger_page(2, 1)[0].position.order_height, 2);
+
+    //and comes back at its place when its fork takes over again
+    let blk_1d = gen_shard_block(1, blk_1a.hash(), vec![]);
+    let blk_1e = gen_shard_block(1, blk_1d.hash(), vec![]);
+    multichain.insert_block_with_parent(blk_1d.clone(), &VersaHash::ShardHash(blk_1a.hash())).unwrap();
+    multichain.insert_block_with_parent(blk_1e.clone(), &VersaHash::ShardHash(blk_1d.hash())).unwrap();
+    let tx_hashes: Vec<H256> = multichain.get_global_ledger().iter().map(|x| x.tx_hash).collect();
+    let expected: Vec<H256> = [0, 1, 3, 2].iter().map(|i| txs[*i].hash()).collect();
+    assert_eq!(tx_hashes, expected);
+
+    //a longer fork of the order chain confirming nothing empties the ledger
+    let mut order_parent = order_genesis;
+    for _ in 0..3 {
+        let block = gen_order_block(order_parent, vec![]);
+        multichain.insert_block_with_parent(block.clone(), &VersaHash::OrderHash(order_parent)).unwrap();
+        order_parent = block.hash();
+    }
+    assert!(multichain.get_global_ledger().is_empty());
+    assert_eq!(multichain.get_global_ledger_size(), 0);
+}
+
+#[test]
+fn test_restore_from_data_dir() {
+    let mut config = Configuration::new();
+    config.shard_num = 2;
+    config.k = 1;
+    let data_dir = format!("./DB/test-restore-{}", H256::random());
+    config.data_dir = Some(data_dir.clone());
+    config.block_reward = 50;
+
+    let (order_tip, shard_tip, confirmed, tx) = {
+        let mut multichain = create_multichain(&config, &[]);
+        let genesis = multichain.get_highest_shard_block(0);
+        //an address of shard 0 under the hash-mod assignment
+        let tx = Transaction::create_coinbase_tx(genesis, &Address::default(), 50);
+        let blk_a = gen_shard_block(0, genesis, vec![tx.clone()]);
+        let blk_b = gen_shard_block(0, blk_a.hash(), vec![]);
+        multichain.insert_block_with_parent(blk_a.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+        multichain.insert_block_with_parent(blk_b.clone(), &VersaHash::ShardHash(blk_a.hash())).unwrap();
+        let order_genesis = multichain.get_highest_order_block();
+        let order_1 = gen_order_block(order_genesis, vec![blk_a.hash()]);
+        multichain.insert_block_with_parent(order_1.clone(), &VersaHash::OrderHash(order_genesis)).unwrap();
+        (order_1.hash(), blk_b.hash(), multichain.get_new_confirmed_shard_blocks(), tx)
+    };
+
+    //a restarted node finds its chains and their bookkeeping again
+    let multichain = create_multichain(&config, &[]);
+    assert_eq!(multichain.get_highest_order_block(), order_tip);
+    assert_eq!(multichain.get_highest_shard_block(0), shard_tip);
+    assert_eq!(multichain.get_shard_size(0), 3);
+    assert_eq!(multichain.get_new_confirmed_shard_blocks(), confirmed);
+    assert!(multichain.get_shard_state(0).contains_tx(&tx.hash()));
+    assert!(multichain.is_tx_anchored(0, &tx.hash(), &order_tip));
+    assert!(!multichain.is_tx_anchored(0, &tx.hash(), &OrderBlock::default().hash()));
+    assert_eq!(multichain.get_global_ledger().len(), 1);
+    drop(multichain);
+    let _ = std::fs::remove_dir_all(data_dir);
+}
+
+#[test]
+fn test_anchoring_follows_order_parent() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.k = 0;
+    let mut multichain = create_multichain(&config, &[]);
+    let genesis = multichain.get_highest_shard_block(0);
+    let order_genesis = multichain.get_highest_order_block();
+    let tx = Transaction::random();
+    let blk_a = gen_shard_block(0, genesis, vec![tx.clone()]);
+    multichain.insert_block_with_parent(blk_a.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+
+    //blk_a is confirmed on a fork of the order chain which loses to a longer one
+    let order_a1 = gen_order_block(order_genesis, vec![blk_a.hash()]);
+    let order_a2 = gen_order_block(order_a1.hash(), vec![]);
+    let order_b1 = gen_order_block(order_genesis, vec![]);
+    let order_b2 = gen_order_block(order_b1.hash(), vec![]);
+    let order_b3 = gen_order_block(order_b2.hash(), vec![]);
+    for block in [&order_a1, &order_a2, &order_b1, &order_b2, &order_b3] {
+        let parent = VersaHash::OrderHash(block.get_parent());
+        multichain.insert_block_with_parent(block.clone(), &parent).unwrap();
+    }
+    assert_eq!(multichain.get_highest_order_block(), order_b3.hash());
+
+    //the anchoring depends on the order parent only, not on the tip
+    assert!(multichain.is_tx_anchored(0, &tx.hash(), &order_a1.hash()));
+    assert!(multichain.is_tx_anchored(0, &tx.hash(), &order_a2.hash()));
+    assert!(!multichain.is_tx_anchored(0, &tx.hash(), &order_b3.hash()));
+    assert!(!multichain.is_tx_anchored(0, &tx.hash(), &order_genesis));
+    //the tip of the order chain still has blk_a to confirm
+    assert_eq!(multichain.get_new_confirmed_shard_blocks(), vec![genesis, blk_a.hash()]);
+}
+
+#[test]
+fn test_heaviest_chain() {
+    let weak: H256 = (&[255u8; 32]).into();
+    let strong: H256 = (&[0u8; 32]).into();
+    assert!(strong.to_work() > weak.to_work());
+    assert_eq!(weak.to_work(), 1);
+
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[15u8; 32]).into();
+    let mut multichain = create_multichain(&config, &[]);
+    let genesis = multichain.get_highest_shard_block(0);
+    //the tip does not depend on the arrival order of equal forks
+    let forks: Vec<VersaBlock> = (0..4).map(|_| gen_shard_block(0, genesis, vec![])).collect();
+    for fork in forks.iter() {
+        multichain.insert_block_with_parent(fork.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+    }
+    let lowest = forks.iter().map(|x| x.hash()).min().unwrap();
+    assert_eq!(multichain.get_highest_shard_block(0), lowest);
+    //extending another fork gives it more work
+    let heaviest = forks.iter().map(|x| x.hash()).max().unwrap();
+    let child = gen_shard_block(0, heaviest, vec![]);
+    multichain.insert_block_with_parent(child.clone(), &VersaHash::ShardHash(heaviest)).unwrap();
+    assert_eq!(multichain.get_highest_shard_block(0), child.hash());
+    assert_eq!(multichain.all_blocks_in_longest_shard_chain_by_shard(0), vec![genesis, heaviest, child.hash()]);
+}
+
+#[test]
+fn test_retarget() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[15u8; 32]).into();
+    config.retarget_window = 3;
+    config.block_interval = 1000;
+    let mut multichain = create_multichain(&config, &[]);
+    let base = config.block_diff;
+    let start = SystemTime::now();
+    let mut parent = multichain.get_highest_shard_block(0);
+    let extend = |multichain: &mut Multichain, parent: &mut H256, millis: u64| {
+        let header = BlockHeader::create(
+            0,
+            H256::random(),
+            *parent,
+            H256::default(),
+            H256::default(),
+            H256::default(),
+            start + Duration::from_millis(millis),
+        );
+        let block = VersaBlock::ShardBlock(ShardBlock::create(header, vec![], 0));
+        multichain.insert_block_with_parent(block.clone(), &VersaHash::ShardHash(*parent)).unwrap();
+        *parent = block.hash();
+    };
+    //blocks twice slower than expected double the target at the end of the window
+    extend(&mut multichain, &mut parent, 0);
+    assert_eq!(multichain.get_next_shard_target(0, &parent), Some(base));
+    extend(&mut multichain, &mut parent, 2000);
+    let eased = base.mul_div(2, 1);
+    assert_eq!(multichain.get_next_shard_target(0, &parent), Some(eased));
+    extend(&mut multichain, &mut parent, 4000);
+    assert_eq!(multichain.get_next_shard_target(0, &parent), Some(eased));
+    //blocks at once lower it by at most 4 times
+    extend(&mut multichain, &mut parent, 4000);
+    extend(&mut multichain, &mut parent, 4000);
+    assert_eq!(multichain.get_next_shard_target(0, &parent), Some(eased.mul_div(1, 4)));
+    assert_eq!(multichain.get_next_shard_target(0, &H256::random()), None);
+}
+
+#[test]
+fn test_reorg_reconciles_mempool() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    //the funding transactions are minted outside the genesis
+    config.synthetic_txs = true;
+    let key = key_pair::random();
+    let user = Address::from_key_pair(&key);
+    let fund_1 = Transaction::create_initial_tx(&user, 100);
+    let fund_2 = Transaction::create_initial_tx(&user, 200);
+    let spend = |fund: &Transaction, value: usize| Transaction::consume(
+        vec![(fund, 0)],
+        vec![&key],
+        vec![(&user, value)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let spend_1 = spend(&fund_1, 99);
+    let spend_2 = spend(&fund_2, 198);
+    let double_spend_2 = spend(&fund_2, 190);
+
+    let mut multichain = create_multichain(&config, &[]);
+    let mut mempool = Mempool::new(&config);
+    let genesis = multichain.get_highest_shard_block(0);
+    let blk_a1 = gen_shard_block(0, genesis, vec![fund_1.clone(), fund_2.clone()]);
+    multichain.insert_block_with_parent(blk_a1.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+    assert_eq!(multichain.take_reorgs(), vec![Reorg {
+        detached: vec![],
+        attached: vec![blk_a1.hash()],
+    }]);
+    mempool.insert_tx(spend_1.clone(), 1).unwrap();
+    mempool.insert_tx(spend_2.clone(), 2).unwrap();
+
+    //a mined transaction leaves the mempool
+    let blk_a2 = gen_shard_block(0, blk_a1.hash(), vec![spend_1.clone()]);
+    multichain.insert_block_with_parent(blk_a2.clone(), &VersaHash::ShardHash(blk_a1.hash())).unwrap();
+    multichain.reconcile_mempool(&mut mempool);
+    assert!(!mempool.check(&spend_1.hash()));
+    assert_eq!(mempool.get_stats().confirmed, 1);
+
+    //a heavier fork drops the pending double spend and brings back spend_1
+    let blk_b2 = loop {
+        let block = gen_shard_block(0, blk_a1.hash(), vec![double_spend_2.clone()]);
+        //an equal fork with a higher hash does not take over
+        if block.hash() > blk_a2.hash() {
+            break block;
+        }
+    };
+    let blk_b3 = gen_shard_block(0, blk_b2.hash(), vec![]);
+    multichain.insert_block_with_parent(blk_b2.clone(), &VersaHash::ShardHash(blk_a1.hash())).unwrap();
+    multichain.insert_block_with_parent(blk_b3.clone(), &VersaHash::ShardHash(blk_b2.hash())).unwrap();
+    let reorgs = multichain.take_reorgs();
+    assert_eq!(reorgs, vec![Reorg {
+        detached: vec![blk_a2.hash()],
+        attached: vec![blk_b2.hash(), blk_b3.hash()],
+    }]);
+    assert_eq!(
+        multichain.all_blocks_in_longest_shard_chain_by_shard(0),
+        vec![genesis, blk_a1.hash(), blk_b2.hash(), blk_b3.hash()]
+    );
+    mempool.reconcile(
+        std::slice::from_ref(&spend_1),
+        std::slice::from_ref(&double_spend_2),
+        multichain.get_shard_state(0),
+    );
+    assert!(mempool.check(&spend_1.hash()));
+    assert!(!mempool.check(&spend_2.hash()));
+    let stats = mempool.get_stats();
+    assert_eq!((stats.restored, stats.double_spent), (1, 1));
+    assert_eq!(mempool.get_entry(&spend_1.hash()).unwrap().fee, 1);
+
+    //a transaction coming back is not a new submission, failing to re-add it counts no rejection
+    let conflict_1 = spend(&fund_1, 100);
+    let before = mempool.get_stats();
+    mempool.reconcile(std::slice::from_ref(&conflict_1), &[], multichain.get_shard_state(0));
+    assert!(!mempool.check(&conflict_1.hash()));
+    assert!(mempool.check(&spend_1.hash()));
+    assert_eq!(mempool.get_stats(), before);
+}
+
+#[test]
+fn test_admit_tx() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    let key = key_pair::random();
+    let user = Address::from_key_pair(&key);
+    let fund = Transaction::create_initial_tx(&user, 100);
+    let spend = |tx: &Transaction, value: usize| Transaction::consume(
+        vec![(tx, 0)],
+        vec![&key],
+        vec![(&user, value)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let parent = spend(&fund, 99);
+    let child = spend(&parent, 79);
+    let mut multichain = create_multichain(&config, std::slice::from_ref(&fund));
+    let mut mempool = Mempool::new(&config);
+
+    //the fee of the child is computed on top of its pending parent
+    assert!(multichain.admit_tx(&mut mempool, parent.clone()).is_ok());
+    assert!(multichain.admit_tx(&mut mempool, child.clone()).is_ok());
+    assert_eq!(mempool.get_entry(&parent.hash()).unwrap().fee, 1);
+    assert_eq!(mempool.get_entry(&child.hash()).unwrap().fee, 20);
+
+    //once mined, the parent is turned away as invalid
+    let genesis = multichain.get_highest_shard_block(0);
+    let block = gen_shard_block(0, genesis, vec![parent.clone()]);
+    multichain.insert_block_with_parent(block, &VersaHash::ShardHash(genesis)).unwrap();
+    multichain.reconcile_mempool(&mut mempool);
+    assert!(multichain.admit_tx(&mut mempool, parent.clone()).is_err());
+    assert!(!mempool.check(&parent.hash()));
+    assert!(mempool.check(&child.hash()));
+    assert_eq!(mempool.get_stats().invalid, 1);
+}
@@ -0,0 +1,270 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        blockchain::Blockchain,
+        configuration::Configuration,
+        multichain::Multichain,
+        state::UtxoState,
+        transaction::{Transaction, TxFlag},
+    },
+    types::{
+        hash::{H256, Hashable},
+        key_pair,
+        merkle::MerkleTree,
+        address::Address,
+    },
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::time::SystemTime;
+
+fn gen_block(shard_parent: H256, txs: Vec<Transaction>) -> VersaBlock {
+    let header = BlockHeader::create(
+        0,
+        H256::default(),
+        shard_parent,
+        MerkleTree::<Transaction>::new(&txs).root(),
+        H256::default(),
+        H256::default(),
+        SystemTime::now(),
+    );
+    VersaBlock::ShardBlock(ShardBlock::create(header, txs, 0))
+}
+
+#[test]
+fn test_utxo_state() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+
+    let mut state = UtxoState::new(0, &Configuration::new());
+    let tx1 = Transaction::create_initial_tx(&user1, 10);
+    let blk1: H256 = (&[11u8; 32]).into();
+    state.apply_block(&blk1, std::slice::from_ref(&tx1)).unwrap();
+    assert!(state.contains_tx(&tx1.hash()));
+
+    //a valid transfer
+    let tx2 = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key1],
+        vec![(&user2, 4), (&user1, 6)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(state.validate_tx(&tx2).is_ok());
+
+    //double spend of the same output in one block
+    let tx3 = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key1],
+        vec![(&user2, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(state.validate_txs(&[tx2.clone(), tx3.clone()]).is_err());
+
+    //chained spends in one block are fine
+    let tx4 = Transaction::consume(
+        vec![(&tx2, 0)],
+        vec![&key2],
+        vec![(&user1, 4)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(state.validate_tx(&tx4).is_err());
+    assert!(state.validate_txs(&[tx2.clone(), tx4.clone()]).is_ok());
+
+    //a bad signature
+    let tx5 = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key2],
+        vec![(&user2, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(state.validate_tx(&tx5).is_err());
+
+    //value imbalance
+    let mut tx6 = tx3.clone();
+    tx6.outputs[0].value = 11;
+    assert!(state.validate_tx(&tx6).is_err());
+
+    //after being applied, the output is spent
+    let blk2: H256 = (&[12u8; 32]).into();
+    state.apply_block(&blk2, std::slice::from_ref(&tx2)).unwrap();
+    assert!(state.validate_tx(&tx3).is_err());
+    assert!(state.validate_tx(&tx4).is_ok());
+
+    //rolling back restores the output
+    assert_eq!(state.undo_block(), Some(blk2));
+    assert!(state.validate_tx(&tx3).is_ok());
+    assert!(!state.contains_tx(&tx2.hash()));
+
+    //packing skips confirmed and invalid transactions
+    let selected = state.select_txs(vec![tx1.clone(), tx3.clone(), tx4.clone(), tx2.clone()]);
+    assert_eq!(selected, vec![tx3]);
+}
+
+#[test]
+fn test_state_reorganization() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_reward = 20;
+    let order_chain = Blockchain::new(VersaBlock::OrderBlock(OrderBlock::default()), &config);
+    let shard_chain = Blockchain::new(gen_block(H256::default(), vec![]), &config);
+    let mut multichain = Multichain::new(order_chain, vec![shard_chain], &config);
+    let genesis = multichain.get_highest_shard_block(0);
+
+    let key1: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let tx_a = Transaction::create_coinbase_tx(genesis, &user1, 10);
+    let tx_b = Transaction::create_coinbase_tx(genesis, &user1, 20);
+
+    let blk_a = gen_block(genesis, vec![tx_a.clone()]);
+    multichain.insert_block_with_parent(blk_a.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+    assert!(multichain.get_shard_state(0).contains_tx(&tx_a.hash()));
+
+    //a longer fork without tx_a takes over
+    let blk_b1 = gen_block(genesis, vec![tx_b.clone()]);
+    let blk_b2 = gen_block(blk_b1.hash(), vec![]);
+    multichain.insert_block_with_parent(blk_b1.clone(), &VersaHash::ShardHash(genesis)).unwrap();
+    //at equal work, the lower hash is the tip
+    let lower_tip = std::cmp::min(blk_a.hash(), blk_b1.hash());
+    assert_eq!(multichain.get_shard_state(0).tip(), Some(lower_tip));
+    assert_eq!(multichain.get_shard_state(0).contains_tx(&tx_a.hash()), lower_tip == blk_a.hash());
+    multichain.insert_block_with_parent(blk_b2.clone(), &VersaHash::ShardHash(blk_b1.hash())).unwrap();
+    let state = multichain.get_shard_state(0);
+    assert_eq!(state.tip(), Some(blk_b2.hash()));
+    assert!(!state.contains_tx(&tx_a.hash()));
+    assert!(state.contains_tx(&tx_b.hash()));
+    assert!(state.get_utxo(&(tx_a.hash(), 0)).is_none());
+    assert!(state.get_utxo(&(tx_b.hash(), 0)).is_some());
+}
+
+#[test]
+fn test_validate_on_fork() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+    let tx1 = Transaction::create_initial_tx(&user1, 10);
+    let mut blockchain = Blockchain::new(gen_block(H256::default(), vec![tx1.clone()]), &config);
+    let genesis = blockchain.tip();
+
+    let tx2 = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key1],
+        vec![(&user2, 4), (&user1, 6)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let tx3 = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key1],
+        vec![(&user2, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let tx4 = Transaction::consume(
+        vec![(&tx2, 0)],
+        vec![&key2],
+        vec![(&user1, 4)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let tx5 = Transaction::consume(
+        vec![(&tx3, 0)],
+        vec![&key2],
+        vec![(&user1, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let blk_a1 = gen_block(genesis, vec![tx2.clone()]);
+    let blk_a2 = gen_block(blk_a1.hash(), vec![tx4.clone()]);
+    let blk_b = gen_block(genesis, vec![tx3.clone()]);
+    blockchain.insert_block_with_parent(blk_a1.clone(), &genesis).unwrap();
+    blockchain.insert_block_with_parent(blk_a2.clone(), &blk_a1.hash()).unwrap();
+    blockchain.insert_block_with_parent(blk_b.clone(), &genesis).unwrap();
+    let mut state = UtxoState::new(0, &config);
+    state.sync(&blockchain.all_blocks_in_longest_chain(), &blockchain).unwrap();
+    assert_eq!(state.tip(), Some(blk_a2.hash()));
+    let chain_to = |hash: H256| blockchain.all_blocks_end_with_block(&hash).unwrap();
+
+    //below the tip, the blocks above the parent are reverted
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx3), &chain_to(genesis), &blockchain).is_ok());
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx4), &chain_to(genesis), &blockchain).is_err());
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx4), &chain_to(blk_a1.hash()), &blockchain).is_ok());
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx3), &chain_to(blk_a1.hash()), &blockchain).is_err());
+
+    //on a side fork, its blocks are replayed as well
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx5), &chain_to(blk_b.hash()), &blockchain).is_ok());
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx2), &chain_to(blk_b.hash()), &blockchain).is_err());
+    assert!(state.validate_txs_on_chain(std::slice::from_ref(&tx5), &chain_to(blk_a2.hash()), &blockchain).is_err());
+
+    //the state itself stays at its tip
+    assert_eq!(state.tip(), Some(blk_a2.hash()));
+    assert!(state.contains_tx(&tx4.hash()));
+    assert!(state.validate_tx(&tx3).is_err());
+}
+
+#[test]
+fn test_signature_replay() {
+    let key1: Ed25519KeyPair = key_pair::random();
+    let key2: Ed25519KeyPair = key_pair::random();
+    let user1 = Address::from_key_pair(&key1);
+    let user2 = Address::from_key_pair(&key2);
+
+    let mut state = UtxoState::new(0, &Configuration::new());
+    let tx1 = Transaction::create_initial_tx(&user1, 10);
+    let tx2 = Transaction::create_initial_tx(&user1, 12);
+    let blk1: H256 = (&[11u8; 32]).into();
+    state.apply_block(&blk1, &[tx1.clone(), tx2.clone()]).unwrap();
+
+    let spend = Transaction::consume(
+        vec![(&tx1, 0)],
+        vec![&key1],
+        vec![(&user2, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(Transaction::verify_owner(&spend, vec![&tx1]));
+    assert!(state.validate_tx(&spend).is_ok());
+
+    //the signature does not carry over to other outputs
+    let mut redirected = spend.clone();
+    redirected.outputs[0].receiver_addr = user1;
+    assert!(!Transaction::verify_owner(&redirected, vec![&tx1]));
+    assert!(state.validate_tx(&redirected).is_err());
+
+    //nor to another spend of an output with the same owner
+    let other = Transaction::consume(
+        vec![(&tx2, 0)],
+        vec![&key1],
+        vec![(&user2, 12)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let mut replayed = other.clone();
+    replayed.inputs[0].sig_ref = spend.inputs[0].sig_ref.clone();
+    assert!(state.validate_tx(&other).is_ok());
+    assert!(state.validate_tx(&replayed).is_err());
+
+    //each input signs its own index
+    let both = Transaction::consume(
+        vec![(&tx1, 0), (&tx2, 0)],
+        vec![&key1, &key1],
+        vec![(&user2, 22)],
+        TxFlag::Domestic,
+    ).unwrap();
+    assert!(state.validate_tx(&both).is_ok());
+    let mut swapped = both.clone();
+    let sig = swapped.inputs[0].sig_ref.clone();
+    swapped.inputs[0].sig_ref = swapped.inputs[1].sig_ref.clone();
+    swapped.inputs[1].sig_ref = sig;
+    assert!(state.validate_tx(&swapped).is_err());
+
+    //a valid signature by a key which does not hash to the spent address
+    let mut stolen = spend.clone();
+    stolen.inputs[0].public_key_ref = key2.public_key().as_ref().to_vec();
+    stolen.inputs[0].sig_ref = Transaction::sign(&stolen, 0, &key2).as_ref().to_vec();
+    assert!(Transaction::verify(&stolen, 0, &stolen.inputs[0].public_key_ref, &stolen.inputs[0].sig_ref));
+    assert!(!Transaction::verify_owner(&stolen, vec![&tx1]));
+    assert!(state.validate_tx(&stolen).is_err());
+}
@@ -0,0 +1,324 @@
+use crate::{
+    sharding_bitcoin::{
+        block::{
+            BlockHeader,
+            ShardBlock,
+            OrderBlock,
+            versa_block::{VersaBlock, VersaHash},
+        },
+        configuration::Configuration,
+        transaction::{Transaction, TxFlag},
+        validator::Validator,
+    },
+    types::{
+        hash::{H256, Hashable},
+        address::Address,
+        key_pair,
+        merkle::MerkleTree,
+        random::Random,
+    },
+};
+use super::common::create_multichain;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+//a shard block header pointing at the order genesis
+fn create_header(config: &Configuration, shard_parent: H256, txs: &[Transaction]) -> BlockHeader {
+    BlockHeader::create(
+        0,
+        OrderBlock::default().hash(),
+        shard_parent,
+        MerkleTree::<Transaction>::new(txs).root(),
+        config.order_diff,
+        config.block_diff,
+        SystemTime::now(),
+    )
+}
+
+#[test]
+fn test_validate_pow() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    config.order_diff = (&[0u8; 32]).into();
+    let validator = Validator::new(&config);
+
+    let header = create_header(&config, H256::random(), &[]);
+    let shard_block = VersaBlock::ShardBlock(ShardBlock::create(header.clone(), vec![], 0));
+    let order_block = VersaBlock::OrderBlock(OrderBlock::create(header.clone(), vec![], 0));
+    assert!(validator.validate_block(&shard_block).is_ok());
+    //no hash can be below a zero difficulty
+    assert!(validator.validate_block(&order_block).is_err());
+
+    config.block_diff = (&[0u8; 32]).into();
+    config.order_diff = (&[255u8; 32]).into();
+    let header = create_header(&config, H256::random(), &[]);
+    let shard_block = VersaBlock::ShardBlock(ShardBlock::create(header.clone(), vec![], 0));
+    let order_block = VersaBlock::OrderBlock(OrderBlock::create(header.clone(), vec![], 0));
+    assert!(validator.validate_block(&shard_block).is_err());
+    assert!(validator.validate_block(&order_block).is_ok());
+}
+
+#[test]
+fn test_validate_target() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+
+    let block = VersaBlock::ShardBlock(ShardBlock::create(create_header(&config, genesis, &[]), vec![], 0));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &block).is_ok());
+    //a block committing an easier target than the chain expects passes the pow check only
+    let mut easier = config.clone();
+    easier.block_diff = (&[255u8; 32]).into();
+    config.block_diff = (&[127u8; 32]).into();
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+    let block = VersaBlock::ShardBlock(ShardBlock::create(create_header(&easier, genesis, &[]), vec![], 0));
+    assert!(validator.validate_block(&block).is_ok());
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &block).is_err());
+    //the targets are part of the hash
+    let timestamp = SystemTime::now();
+    let header = |shard_target: H256| {
+        BlockHeader::create(0, genesis, genesis, H256::default(), config.order_diff, shard_target, timestamp)
+    };
+    assert_eq!(header(config.block_diff).hash(), header(config.block_diff).hash());
+    assert_ne!(header(config.block_diff).hash(), header(easier.block_diff).hash());
+}
+
+#[test]
+fn test_validate_merkle_root() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    config.order_diff = (&[255u8; 32]).into();
+    let validator = Validator::new(&config);
+
+    let txs = vec![Transaction::random()];
+    let header = create_header(&config, H256::random(), &txs);
+    let block = ShardBlock::create(header.clone(), txs, 0);
+    assert!(validator.validate_block(&VersaBlock::ShardBlock(block)).is_ok());
+    let swapped = ShardBlock::create(header, vec![Transaction::random()], 0);
+    assert!(validator.validate_block(&VersaBlock::ShardBlock(swapped)).is_err());
+}
+
+#[test]
+fn test_validate_block_reward() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+
+    let miner = Address::from_key_pair(&key_pair::random());
+    let reward_tx = Transaction::create_coinbase_tx(genesis, &miner, 0);
+    let block = |coinbase_addr: Address| {
+        let mut header = create_header(&config, genesis, std::slice::from_ref(&reward_tx));
+        header.set_coinbase_addr(coinbase_addr);
+        VersaBlock::ShardBlock(ShardBlock::create(header, vec![reward_tx.clone()], 0))
+    };
+    assert!(validator.validate_block(&block(miner)).is_ok());
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &block(miner)).is_ok());
+    //the reward must go to the address committed in the header
+    assert!(validator.validate_block(&block(Address::default())).is_err());
+}
+
+#[test]
+fn test_validate_with_parent() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    let key = key_pair::random();
+    let addr = Address::from_key_pair(&key);
+    let initial_tx = Transaction::create_initial_tx(&addr, 10);
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, std::slice::from_ref(&initial_tx))));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+
+    //value is only minted by the genesis allocation
+    let minting_tx = Transaction::create_initial_tx(&addr, 5);
+    let block = VersaBlock::ShardBlock(ShardBlock::create(
+        create_header(&config, genesis, std::slice::from_ref(&minting_tx)),
+        vec![minting_tx],
+        0,
+    ));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &block).is_err());
+
+    let spend_tx = Transaction::consume(
+        vec![(&initial_tx, 0)],
+        vec![&key],
+        vec![(&addr, 10)],
+        TxFlag::Domestic,
+    ).unwrap();
+    let block = VersaBlock::ShardBlock(ShardBlock::create(
+        create_header(&config, genesis, std::slice::from_ref(&spend_tx)),
+        vec![spend_tx.clone()],
+        0,
+    ));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &block).is_ok());
+    multichain
+        .lock()
+        .unwrap()
+        .insert_block_with_parent(block.clone(), &VersaHash::ShardHash(genesis))
+        .unwrap();
+    //the same transaction cannot be confirmed twice on the same chain
+    let dup_block = VersaBlock::ShardBlock(ShardBlock::create(
+        create_header(&config, block.hash(), std::slice::from_ref(&spend_tx)),
+        vec![spend_tx.clone()],
+        0,
+    ));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &dup_block).is_err());
+    //but it can be confirmed on a fork
+    let fork_block = VersaBlock::ShardBlock(ShardBlock::create(
+        create_header(&config, genesis, std::slice::from_ref(&spend_tx)),
+        vec![spend_tx],
+        1,
+    ));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &fork_block).is_ok());
+    //a shard block waits for its order parent
+    let order_parent = H256::random();
+    let header = BlockHeader::create(
+        0,
+        order_parent,
+        genesis,
+        MerkleTree::<Transaction>::new(&[]).root(),
+        config.order_diff,
+        config.block_diff,
+        SystemTime::now(),
+    );
+    let waiting = VersaBlock::ShardBlock(ShardBlock::create(header, vec![], 0));
+    assert!(validator.get_missing_refs(&multichain.lock().unwrap(), &fork_block).is_empty());
+    assert_eq!(
+        validator.get_missing_refs(&multichain.lock().unwrap(), &waiting),
+        vec![VersaHash::OrderHash(order_parent)]
+    );
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &waiting).is_err());
+}
+
+#[test]
+fn test_validate_confirmed_shard_blocks() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.k = 1;
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+    let order_genesis = multichain.lock().unwrap().get_highest_order_block();
+
+    let blk_a = VersaBlock::ShardBlock(ShardBlock::create(create_header(&config, genesis, &[]), vec![], 0));
+    let blk_b = VersaBlock::ShardBlock(ShardBlock::create(create_header(&config, blk_a.hash(), &[]), vec![], 0));
+    for (block, parent) in [(&blk_a, genesis), (&blk_b, blk_a.hash())] {
+        multichain
+            .lock()
+            .unwrap()
+            .insert_block_with_parent(block.clone(), &VersaHash::ShardHash(parent))
+            .unwrap();
+    }
+    let order_block = |parent: H256, refs: Vec<H256>| {
+        let header = BlockHeader::create(
+            0,
+            parent,
+            H256::random(),
+            H256::default(),
+            config.order_diff,
+            config.block_diff,
+            SystemTime::now(),
+        );
+        VersaBlock::OrderBlock(OrderBlock::create(header, refs, 0))
+    };
+
+    let valid = order_block(order_genesis, vec![blk_a.hash()]);
+    assert!(validator.get_missing_refs(&multichain.lock().unwrap(), &valid).is_empty());
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &valid).is_ok());
+    assert!(validator.get_shallow_refs(&multichain.lock().unwrap(), &valid).is_empty());
+    //blk_b is the tip, not k-deep yet
+    let shallow = order_block(order_genesis, vec![blk_b.hash()]);
+    assert_eq!(validator.get_shallow_refs(&multichain.lock().unwrap(), &shallow), vec![blk_b.hash()]);
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &shallow).is_err());
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &order_block(order_genesis, vec![blk_a.hash(), blk_a.hash()])).is_err());
+    //an unknown shard block is missing rather than invalid
+    let unknown = H256::random();
+    let waiting = order_block(order_genesis, vec![unknown]);
+    assert_eq!(
+        validator.get_missing_refs(&multichain.lock().unwrap(), &waiting),
+        vec![VersaHash::ShardHash(unknown)]
+    );
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &waiting).is_err());
+
+    multichain
+        .lock()
+        .unwrap()
+        .insert_block_with_parent(valid.clone(), &VersaHash::OrderHash(order_genesis))
+        .unwrap();
+    //blk_a is already confirmed by the parent
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &order_block(valid.hash(), vec![blk_a.hash()])).is_err());
+    assert_eq!(multichain.lock().unwrap().get_new_confirmed_shard_blocks(), vec![genesis]);
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &order_block(valid.hash(), vec![genesis])).is_ok());
+    //blk_a is not confirmed on another branch of the order chain
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &order_block(order_genesis, vec![blk_a.hash()])).is_ok());
+
+    //a block of a shorter fork of the shard is judged by the blocks known on top of it
+    let blk_c = VersaBlock::ShardBlock(ShardBlock::create(create_header(&config, genesis, &[]), vec![], 1));
+    let blk_d = VersaBlock::ShardBlock(ShardBlock::create(create_header(&config, blk_c.hash(), &[]), vec![], 1));
+    for (block, parent) in [(&blk_c, genesis), (&blk_d, blk_c.hash())] {
+        multichain
+            .lock()
+            .unwrap()
+            .insert_block_with_parent(block.clone(), &VersaHash::ShardHash(parent))
+            .unwrap();
+    }
+    let fork = order_block(valid.hash(), vec![blk_c.hash()]);
+    assert!(validator.get_shallow_refs(&multichain.lock().unwrap(), &fork).is_empty());
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &fork).is_ok());
+}
+
+#[test]
+fn test_validate_timestamp() {
+    let mut config = Configuration::new();
+    config.shard_num = 1;
+    config.block_diff = (&[255u8; 32]).into();
+    config.max_time_drift = 1000;
+    config.median_time_span = 3;
+    let multichain = Arc::new(Mutex::new(create_multichain(&config, &[])));
+    let validator = Validator::new(&config);
+    let genesis = multichain.lock().unwrap().get_highest_shard_block(0);
+    let start = SystemTime::now() - Duration::from_secs(10);
+    let shard_block = |parent: H256, timestamp: SystemTime| {
+        let header = BlockHeader::create(
+            0,
+            OrderBlock::default().hash(),
+            parent,
+            MerkleTree::<Transaction>::new(&[]).root(),
+            config.order_diff,
+            config.block_diff,
+            timestamp,
+        );
+        VersaBlock::ShardBlock(ShardBlock::create(header, vec![], 0))
+    };
+
+    let future = shard_block(genesis, SystemTime::now() + Duration::from_secs(3600));
+    //a block ahead of the clock is not invalid, only too early
+    assert!(validator.validate_block(&future).is_ok());
+    assert!(validator.validate_time_drift(&future).is_err());
+    let mut parent = genesis;
+    for millis in [3000, 1000, 2000] {
+        let block = shard_block(parent, start + Duration::from_millis(millis));
+        assert!(validator.validate_block(&block).is_ok());
+        multichain
+            .lock()
+            .unwrap()
+            .insert_block_with_parent(block.clone(), &VersaHash::ShardHash(parent))
+            .unwrap();
+        parent = block.hash();
+    }
+    //the median of the last 3 timestamps is the 2000th millisecond
+    let at_median = shard_block(parent, start + Duration::from_millis(2000));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &at_median).is_err());
+    let after_median = shard_block(parent, start + Duration::from_millis(2001));
+    assert!(validator.validate_with_parent(&multichain.lock().unwrap(), &after_median).is_ok());
+}
@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
 use std::convert::TryInto;
 use crate::types::hash::*;
+use ring::signature::{Ed25519KeyPair, KeyPair};
 
 // 20-byte address
 #[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Hash, Default, Copy)]
@@ -58,6 +59,22 @@ impl Address {
         let address: Address = std::convert::From::from(address_slice);
         address
     }
+
+    /// The address of a public key given as bytes, none if it is not an Ed25519 public key
+    pub fn from_public_key(bytes: &[u8]) -> Option<Address> {
+        let bytes: &[u8; 32] = bytes.try_into().ok()?;
+        Some(Address::from_public_key_bytes(bytes))
+    }
+
+    pub fn from_key_pair(key: &Ed25519KeyPair) -> Address {
+        Address::from_public_key(key.public_key().as_ref()).unwrap()
+    }
+}
+
+impl std::convert::AsRef<[u8]> for Address {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
 }
 // DO NOT CHANGE THIS COMMENT, IT IS FOR AUTOGRADER. BEFORE TEST
 
@@ -26,16 +26,21 @@ impl<T> Database<T>
     where T: Hashable + Default + Serialize + DeserializeOwned,
 {
     pub fn new(path: String) -> Self {
+        Self::open(format!("./DB/{}", path))
+    }
+
+    /// Open the database at the given path, keeping the data stored by an earlier run
+    pub fn open(path: String) -> Self {
         let mut options = Options::default();
         options.create_if_missing(true);
-        let absolute_path = format!("./DB/{}", path);
-        let db = DB::open(&options, absolute_path.clone()).unwrap();
+        let db = DB::open(&options, path.clone()).unwrap();
+        let counter = db.iterator(IteratorMode::Start).count();
 
         Self {
-            path: absolute_path,
+            path,
             db,
             sample_data: T::default(),
-            counter: 0,
+            counter,
         }
     }
 
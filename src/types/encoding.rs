@@ -0,0 +1,213 @@
+//! Canonical binary encoding of the objects which are hashed, signed or sent to peers.
+//!
+//! An encoded object starts with the version byte `ENCODING_VERSION`, followed by its fields
+//! in declaration order:
+//! - integers are fixed-width big-endian, `usize` is encoded as `u64`, `bool` as one byte 0 or 1
+//! - `H256` is its 32 raw bytes, `Address` its 20 raw bytes
+//! - sequences, byte strings included, are a `u32` length followed by the elements
+//! - strings are length-prefixed UTF-8 bytes
+//! - enums are a one-byte variant tag followed by the fields of the variant
+//!
+//! Decoding rejects an unknown version, unknown tags, truncated input and trailing bytes,
+//! so every object has exactly one encoding.
+use crate::types::{
+    hash::H256,
+    address::Address,
+};
+
+pub const ENCODING_VERSION: u8 = 1;
+
+/// An object with a canonical encoding
+pub trait Encodable {
+    /// Append the fields of the object, without the version byte
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// An object which can be rebuilt from its canonical encoding
+pub trait Decodable: Sized {
+    fn decode(reader: &mut Reader) -> Result<Self, String>;
+}
+
+/// Cursor over an encoded buffer
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < len {
+            return Err(String::from("Unexpected end of input"));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+/// The versioned encoding of an object
+pub fn serialize<T: Encodable + ?Sized>(value: &T) -> Vec<u8> {
+    let mut out = vec![ENCODING_VERSION];
+    value.encode(&mut out);
+    out
+}
+
+/// Rebuild an object from its versioned encoding
+pub fn deserialize<T: Decodable>(bytes: &[u8]) -> Result<T, String> {
+    let mut reader = Reader::new(bytes);
+    let version = u8::decode(&mut reader)?;
+    if version != ENCODING_VERSION {
+        return Err(format!("Unsupported encoding version {}", version));
+    }
+    let value = T::decode(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(String::from("Trailing bytes after the encoded object"));
+    }
+    Ok(value)
+}
+
+/// SHA256 of the versioned encoding of an object
+pub fn digest<T: Encodable + ?Sized>(value: &T) -> H256 {
+    ring::digest::digest(&ring::digest::SHA256, &serialize(value)).into()
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Encodable for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+
+            impl Decodable for $t {
+                fn decode(reader: &mut Reader) -> Result<Self, String> {
+                    let bytes = reader.read_bytes(std::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_be_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u16, u32, u64, u128);
+
+impl Encodable for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+}
+
+impl Decodable for usize {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        usize::try_from(u64::decode(reader)?).map_err(|e| e.to_string())
+    }
+}
+
+impl Encodable for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Decodable for bool {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match u8::decode(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            x => Err(format!("Invalid boolean {}", x)),
+        }
+    }
+}
+
+impl Encodable for H256 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0);
+    }
+}
+
+impl Decodable for H256 {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let bytes: &[u8; 32] = reader.read_bytes(32)?.try_into().unwrap();
+        Ok(bytes.into())
+    }
+}
+
+impl Encodable for Address {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_ref());
+    }
+}
+
+impl Decodable for Address {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let bytes: &[u8; 20] = reader.read_bytes(20)?.try_into().unwrap();
+        Ok(bytes.into())
+    }
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) {
+    u32::try_from(len)
+        .expect("Sequence is too long to encode")
+        .encode(out);
+}
+
+impl<T: Encodable> Encodable for [T] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode(out);
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        let len = u32::decode(reader)? as usize;
+        //the length is not trusted for the allocation, a short input fails while reading
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
+}
+
+impl Encodable for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out);
+    }
+}
+
+impl Decodable for String {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        String::from_utf8(Vec::<u8>::decode(reader)?).map_err(|e| e.to_string())
+    }
+}
+
+impl<A: Encodable, B: Encodable> Encodable for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A: Decodable, B: Decodable> Decodable for (A, B) {
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
@@ -87,6 +87,47 @@ impl H256 {
     pub fn get_mem_size() -> usize {
         std::mem::size_of::<u8>() * 32
     }
+
+    /// Scale the hash, read as a 256-bit integer, by `num / den`, saturating at the maximum
+    pub fn mul_div(&self, num: u64, den: u64) -> H256 {
+        assert!(den != 0, "Division by zero");
+        //big-endian 64-bit limbs, with one more limb for the carry
+        let mut limbs = [0u64; 5];
+        let mut carry: u128 = 0;
+        for i in (0..4).rev() {
+            let mut limb = [0u8; 8];
+            limb.copy_from_slice(&self.0[i * 8..i * 8 + 8]);
+            let product = u64::from_be_bytes(limb) as u128 * num as u128 + carry;
+            limbs[i + 1] = product as u64;
+            carry = product >> 64;
+        }
+        limbs[0] = carry as u64;
+        let mut remainder: u128 = 0;
+        for limb in limbs.iter_mut() {
+            let dividend = (remainder << 64) | *limb as u128;
+            *limb = (dividend / den as u128) as u64;
+            remainder = dividend % den as u128;
+        }
+        if limbs[0] != 0 {
+            return H256([255u8; 32]);
+        }
+        let mut result = [0u8; 32];
+        for i in 0..4 {
+            result[i * 8..i * 8 + 8].copy_from_slice(&limbs[i + 1].to_be_bytes());
+        }
+        H256(result)
+    }
+
+    /// The expected number of hashes needed to meet this target, 
+    /// computed from its most significant 128 bits
+    pub fn to_work(&self) -> u128 {
+        let mut top = [0u8; 16];
+        top.copy_from_slice(&self.0[..16]);
+        match u128::from_be_bytes(top).checked_add(1) {
+            Some(divisor) => u128::MAX / divisor,
+            None => 1,
+        }
+    }
 }
 
 
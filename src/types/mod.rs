@@ -3,4 +3,5 @@ pub mod hash;
 pub mod merkle;
 pub mod key_pair;
 pub mod random;
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod encoding;
\ No newline at end of file